@@ -0,0 +1,92 @@
+//! Rewriting of the loops in the functions wrapped in `enable!`.
+
+use proc_macro2::{Group, Spacing, TokenStream, TokenTree};
+use quote::quote;
+use syn::parse::Parser;
+
+use crate::parse;
+
+/// Replaces every loop statement starting with `do` in `input`, including those nested in blocks,
+/// with an invocation of the `macro_rules!` implementation of `do_while!` through `krate`. The
+/// arguments of macro calls are left as they are.
+pub(crate) fn rewrite(krate: &TokenStream, input: TokenStream) -> TokenStream {
+    let mut tokens: Vec<TokenTree> = Vec::new();
+    for token in input {
+        match token {
+            TokenTree::Group(group) if !is_macro_call(&tokens) => {
+                let mut rewritten = Group::new(group.delimiter(), rewrite(krate, group.stream()));
+                rewritten.set_span(group.span());
+                tokens.push(TokenTree::Group(rewritten));
+            }
+            token => tokens.push(token),
+        }
+    }
+
+    let mut output: Vec<TokenTree> = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        if !matches!(&tokens[index], TokenTree::Ident(ident) if ident == "do") {
+            output.push(tokens[index].clone());
+            index += 1;
+            continue;
+        }
+
+        let label = take_label(&mut output);
+        let attrs = take_attrs(&mut output);
+        let rest: TokenStream = tokens[index..].iter().cloned().collect();
+        match parse::parse_loop_statement.parse2(rest) {
+            Ok((body, after)) => {
+                let end = tokens.len() - after.into_iter().count();
+                output.extend(quote! {
+                    #krate::__do_while! { #( #attrs )* #( #label )* #body }
+                });
+                index = end;
+            }
+            Err(error) => {
+                // The rest of the block can't be split into statements, so it is replaced by the
+                // error
+                output.extend(error.to_compile_error());
+                break;
+            }
+        }
+    }
+    output.into_iter().collect()
+}
+
+/// Returns whether a group following `tokens` holds the arguments of a macro call, as in `name!(...)`
+/// or `macro_rules! name { ... }`.
+fn is_macro_call(tokens: &[TokenTree]) -> bool {
+    match tokens {
+        [.., TokenTree::Ident(_), TokenTree::Punct(bang)] => bang.as_char() == '!',
+        [.., TokenTree::Ident(keyword), TokenTree::Punct(bang), TokenTree::Ident(_)] => {
+            keyword == "macro_rules" && bang.as_char() == '!'
+        }
+        _ => false,
+    }
+}
+
+/// Removes a loop label (`'label:`) from the end of `output` and returns it.
+fn take_label(output: &mut Vec<TokenTree>) -> Vec<TokenTree> {
+    match output.as_slice() {
+        [.., TokenTree::Punct(quote), TokenTree::Ident(_), TokenTree::Punct(colon)]
+            if quote.as_char() == '\''
+                && quote.spacing() == Spacing::Joint
+                && colon.as_char() == ':' =>
+        {
+            output.split_off(output.len() - 3)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Removes the outer attributes (`#[...]`) at the end of `output` and returns them.
+fn take_attrs(output: &mut Vec<TokenTree>) -> Vec<TokenTree> {
+    let mut start = output.len();
+    while let [.., TokenTree::Punct(hash), TokenTree::Group(_)] = &output[..start] {
+        if hash.as_char() != '#' {
+            break;
+        }
+        start -= 2;
+    }
+    output.split_off(start)
+}
@@ -0,0 +1,62 @@
+//! Procedural macros for the [`do_while`](https://docs.rs/do_while) crate.
+//!
+//! These are used by `do_while` when its `proc-macro` feature is enabled, and shouldn't be used
+//! directly.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::ParseStream;
+use syn::{bracketed, parse_macro_input};
+
+mod enable;
+mod parse;
+
+/// Checks a `do_while!` invocation, and expands it with the `macro_rules!` implementation.
+///
+/// The input is the path of the `do_while` crate in brackets, followed by the contents of the
+/// invocation. Malformed loops are reported with an error pointing at the mistake instead of a
+/// generic "no rules expected the token" error.
+#[proc_macro]
+pub fn check(input: TokenStream) -> TokenStream {
+    let (krate, tokens) = parse_macro_input!(input with split_crate);
+    let invocation = match syn::parse2::<parse::Invocation>(tokens) {
+        Ok(invocation) => invocation,
+        Err(error) => return error.into_compile_error().into(),
+    };
+
+    // Each loop is expanded on its own, so that neither the number of loops nor the statements
+    // between them add to the recursion depth
+    let statements = invocation
+        .statements
+        .into_iter()
+        .map(|statement| match statement {
+            parse::Statement::Loop(tokens) => quote! { #krate::__do_while! { #tokens } },
+            parse::Statement::Other(tokens) => tokens,
+        });
+    quote! {
+        #( #statements )*
+    }
+    .into()
+}
+
+/// Rewrites the loops in the functions given to `enable!`.
+///
+/// The input is the path of the `do_while` crate in brackets, followed by the contents of the
+/// invocation.
+#[proc_macro]
+pub fn enable(input: TokenStream) -> TokenStream {
+    let (krate, tokens) = parse_macro_input!(input with split_crate);
+    enable::rewrite(&krate, tokens).into()
+}
+
+/// Splits the bracketed path of the `do_while` crate, which the `macro_rules!` wrappers pass as
+/// `[$crate]`, from the rest of the input.
+fn split_crate(
+    input: ParseStream,
+) -> syn::Result<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    let content;
+    bracketed!(content in input);
+    let krate = content.parse()?;
+    let tokens = input.parse()?;
+    Ok((krate, tokens))
+}
@@ -0,0 +1,457 @@
+//! Parser for the grammar of `do_while!` invocations.
+//!
+//! The parser only checks the structure of the loops, so that mistakes can be reported with an
+//! error pointing at them. Bodies, conditions and other statements are left for rustc to check once
+//! the invocation has been expanded by the `macro_rules!` implementation.
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::quote;
+use syn::buffer::Cursor;
+use syn::parse::discouraged::Speculative;
+use syn::parse::{Parse, ParseStream};
+use syn::{
+    parenthesized, token, Attribute, Error, Expr, Ident, Lifetime, Pat, Result, Stmt, Token,
+};
+
+mod kw {
+    syn::custom_keyword!(repeat);
+    syn::custom_keyword!(until);
+    syn::custom_keyword!(defer);
+    syn::custom_keyword!(finally);
+    syn::custom_keyword!(max);
+}
+
+/// The contents of a `do_while!` invocation: loops and other statements, in any order.
+pub(crate) struct Invocation {
+    pub(crate) statements: Vec<Statement>,
+}
+
+/// The tokens of a single statement of an invocation.
+pub(crate) enum Statement {
+    Loop(TokenStream),
+    Other(TokenStream),
+}
+
+/// Where a loop is being parsed.
+#[derive(Clone, Copy)]
+enum Context {
+    /// A `do_while!` invocation, and whether the loop is its first statement.
+    Invocation { first: bool },
+    /// A function body rewritten by `enable!`, where the loop is a single statement.
+    Statement,
+}
+
+impl Parse for Invocation {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut statements = Vec::new();
+        while !input.is_empty() {
+            let begin = input.cursor();
+            if starts_loop(input) {
+                let first = statements.is_empty();
+                let tokens = parse_loop(input, Context::Invocation { first })?;
+                statements.push(Statement::Loop(tokens));
+            } else {
+                parse_stmt(input)?;
+                statements.push(Statement::Other(tokens_between(begin, input.cursor())));
+            }
+        }
+        Ok(Invocation { statements })
+    }
+}
+
+/// Returns whether the next statement is a loop, looking past its attributes and label.
+fn starts_loop(input: ParseStream) -> bool {
+    let fork = input.fork();
+    if fork.call(Attribute::parse_outer).is_err() {
+        return false;
+    }
+    if fork.peek(Lifetime) && fork.peek2(Token![:]) {
+        let _ = fork.parse::<Lifetime>();
+        let _ = fork.parse::<Token![:]>();
+    }
+    if fork.peek(Token![do]) {
+        return true;
+    }
+    if !fork.peek(kw::repeat) {
+        return false;
+    }
+
+    // `repeat` isn't a keyword, so `repeat(...)` is an ordinary statement unless the brackets hold
+    // init and step clauses
+    let Some((_, rest)) = fork.cursor().ident() else {
+        return false;
+    };
+    if rest
+        .punct()
+        .is_some_and(|(punct, _)| punct.as_char() == '|')
+    {
+        return true;
+    }
+    if rest.group(Delimiter::Brace).is_some() {
+        return true;
+    }
+    rest.group(Delimiter::Parenthesis)
+        .and_then(|(inside, _, _)| inside.ident())
+        .is_some_and(|(ident, _)| ident == "let")
+}
+
+/// Parses a loop statement of a function body rewritten by `enable!`, and returns its tokens as
+/// [`parse_loop`] does, along with the tokens after it.
+pub(crate) fn parse_loop_statement(input: ParseStream) -> Result<(TokenStream, TokenStream)> {
+    let tokens = parse_loop(input, Context::Statement)?;
+    Ok((tokens, input.parse()?))
+}
+
+/// Parses a single loop, and returns its tokens to be passed to the `macro_rules!` implementation.
+fn parse_loop(input: ParseStream, context: Context) -> Result<TokenStream> {
+    let begin = input.cursor();
+    input.call(Attribute::parse_outer)?;
+    let label = if input.peek(Lifetime) {
+        let label: Lifetime = input.parse()?;
+        input.parse::<Token![:]>()?;
+        Some(label)
+    } else {
+        None
+    };
+
+    let repeat = input.peek(kw::repeat);
+    if repeat {
+        input.parse::<kw::repeat>()?;
+    } else {
+        input.parse::<Token![do]>()?;
+    }
+
+    let mut counter = false;
+    if input.peek(Token![|]) {
+        input.parse::<Token![|]>()?;
+        expect::<Ident>(input, "expected the name of the iteration counter")?;
+        expect::<Token![|]>(
+            input,
+            "expected `|` after the name of the iteration counter",
+        )?;
+        counter = true;
+    }
+
+    if input.peek(token::Paren) {
+        let content;
+        let parens = parenthesized!(content in input);
+        if content.peek(Token![let]) {
+            parse_init_step(&content)?;
+        } else if let Some(label) = label {
+            return Err(Error::new(
+                label.span(),
+                "loops driven by `ControlFlow` can't have a label",
+            ));
+        } else if counter {
+            return Err(Error::new(
+                parens.span.join(),
+                "loops driven by `ControlFlow` can't have an iteration counter",
+            ));
+        } else if let Context::Invocation { first: false } = context {
+            return Err(Error::new(
+                parens.span.join(),
+                "a loop driven by `ControlFlow` has to be the only statement of its invocation",
+            ));
+        } else {
+            parse_control_flow_state(&content)?;
+            parse_control_flow_rest(input, context)?;
+            return Ok(tokens_between(begin, input.cursor()));
+        }
+    }
+
+    parse_block(input, "expected a block `{ ... }` as the loop body")?;
+
+    let mut defer = false;
+    let mut finally = false;
+    loop {
+        if input.peek(kw::defer) {
+            let keyword: kw::defer = input.parse()?;
+            if defer || finally {
+                return Err(Error::new(
+                    keyword.span,
+                    "a loop can only have one `defer` block, which goes before `finally`",
+                ));
+            }
+            parse_captures(input)?;
+            parse_block(input, "expected a block `{ ... }` after `defer`")?;
+            defer = true;
+        } else if input.peek(kw::finally) {
+            let keyword: kw::finally = input.parse()?;
+            if finally {
+                return Err(Error::new(
+                    keyword.span,
+                    "a loop can only have one `finally` block",
+                ));
+            }
+            parse_captures(input)?;
+            parse_block(input, "expected a block `{ ... }` after `finally`")?;
+            finally = true;
+        } else {
+            break;
+        }
+    }
+
+    while input.peek(Token![let]) {
+        input.parse::<Token![let]>()?;
+        Pat::parse_multi_with_leading_vert(input)?;
+        expect::<Token![=]>(input, "expected `=` after the pattern of the `let` binding")?;
+        input.parse::<Expr>()?;
+        expect::<Token![;]>(input, "expected `;` after the `let` binding")?;
+    }
+
+    let (condition, keyword) = if input.peek(Token![while]) && !repeat {
+        let keyword: Token![while] = input.parse()?;
+        ("while-condition", keyword.span)
+    } else if input.peek(kw::until) {
+        let keyword: kw::until = input.parse()?;
+        ("until-condition", keyword.span)
+    } else if input.peek(Token![,]) {
+        return Err(input.error("unexpected `,` after the loop body"));
+    } else if repeat {
+        return Err(input.error("expected `until` after the body of a `repeat` loop"));
+    } else {
+        return Err(input.error("expected `while` or `until` after the loop body"));
+    };
+
+    let head = tokens_between(begin, input.cursor());
+    let cond_begin = input.cursor();
+    let is_let = input.peek(Token![let]);
+    if is_let {
+        if condition == "until-condition" {
+            return Err(
+                input.error("`until` can't be followed by a `let` pattern, use `while let`")
+            );
+        }
+        input.parse::<Token![let]>()?;
+        Pat::parse_multi_with_leading_vert(input)?;
+        expect::<Token![=]>(input, "expected `=` after the pattern of `while let`")?;
+    } else if input.is_empty() || input.peek(Token![;]) || input.peek(Token![,]) {
+        return Err(input.error(format!("expected a {condition}")));
+    }
+    input.parse::<Expr>()?;
+    let mut cond = tokens_between(cond_begin, input.cursor());
+    let cond_end = input.cursor();
+
+    let mut max = None;
+    let mut else_block = false;
+    if input.peek(Token![,]) && input.peek2(kw::max) {
+        input.parse::<Token![,]>()?;
+        max = Some(input.parse::<kw::max>()?);
+        expect::<Expr>(
+            input,
+            "expected the maximum number of iterations after `max`",
+        )?;
+    }
+
+    if input.peek(Token![;]) {
+        input.parse::<Token![;]>()?;
+        if let (Context::Invocation { .. }, true) = (context, input.peek(Token![else])) {
+            parse_else_value(input)?;
+        }
+    } else if input.peek(Token![,]) {
+        let comma: Token![,] = input.parse()?;
+        if !input.peek(Token![do]) {
+            let expected = if max.is_some() {
+                "expected `do { ... }` after `,`"
+            } else {
+                "expected `do { ... }` or `max limit` after `,`"
+            };
+            return Err(Error::new(comma.span, expected));
+        }
+        input.parse::<Token![do]>()?;
+        parse_block(input, "expected a block `{ ... }` after `, do`")?;
+        if input.peek(Token![else]) {
+            match context {
+                Context::Invocation { .. } => parse_else(input)?,
+                Context::Statement => {
+                    input.parse::<Token![else]>()?;
+                    parse_block(input, "expected a block `{ ... }` after `else`")?;
+                }
+            }
+        }
+    } else if input.peek(Token![else]) {
+        if max.is_some() {
+            return Err(input
+                .error("an `else` block can't follow a `max` clause, use `; else value` instead"));
+        }
+        input.parse::<Token![else]>()?;
+        parse_block(input, "expected a block `{ ... }` after `else`")?;
+        else_block = true;
+    } else {
+        return Err(Error::new_spanned(
+            cond,
+            format!("expected `;` or `, do {{ ... }}` after {condition}"),
+        ));
+    }
+
+    if let (Some(max), false) = (max, input.is_empty()) {
+        return Err(Error::new(
+            max.span,
+            "a loop with a `max` clause evaluates to a `Result`, so it has to be the last statement",
+        ));
+    }
+
+    // Conditions that the `macro_rules!` implementation would have to collect one token at a time
+    // are passed to it in brackets after `@`, preceded by the `if` to check a `let` condition with.
+    // The `if` is given the span of `while`, so that let chains are allowed or not according to the
+    // edition of the caller.
+    if is_let {
+        let if_token = Ident::new("if", keyword);
+        cond = quote!(@ [#if_token] [#cond]);
+    } else if else_block {
+        cond = quote!(@ [] [#cond]);
+    }
+    let tail = tokens_between(cond_end, input.cursor());
+    Ok(quote!(#head #cond #tail))
+}
+
+/// Parses the init and step clauses in `do (let mut i = 0; i += 1) { ... }`.
+fn parse_init_step(input: ParseStream) -> Result<()> {
+    input.parse::<Stmt>()?;
+    if input.is_empty() {
+        return Err(input.error("expected a step expression after the init clause"));
+    }
+    input.parse::<Expr>()?;
+    if !input.is_empty() {
+        return Err(input.error("unexpected tokens after the step expression"));
+    }
+    Ok(())
+}
+
+/// Parses the state in `do (state = init) { ... } while condition;`.
+fn parse_control_flow_state(input: ParseStream) -> Result<()> {
+    Pat::parse_single(input)?;
+    expect::<Token![=]>(
+        input,
+        "expected `=` and the initial state after the state pattern",
+    )?;
+    input.parse::<Expr>()?;
+    if !input.is_empty() {
+        return Err(input.error("unexpected tokens after the initial state"));
+    }
+    Ok(())
+}
+
+/// Parses the rest of a loop driven by `ControlFlow`, which has to be the only statement of a
+/// `do_while!` invocation.
+fn parse_control_flow_rest(input: ParseStream, context: Context) -> Result<()> {
+    parse_block(input, "expected a block `{ ... }` as the loop body")?;
+    if !input.peek(Token![while]) {
+        return Err(
+            input.error("expected `while` after the body of a loop driven by `ControlFlow`")
+        );
+    }
+    input.parse::<Token![while]>()?;
+    let begin = input.cursor();
+    input.parse::<Expr>()?;
+    if !input.peek(Token![;]) {
+        return Err(Error::new_spanned(
+            tokens_between(begin, input.cursor()),
+            "expected `;` after while-condition",
+        ));
+    }
+    input.parse::<Token![;]>()?;
+    if let (Context::Invocation { .. }, false) = (context, input.is_empty()) {
+        return Err(input.error(
+            "a loop driven by `ControlFlow` has to be the only statement of its invocation",
+        ));
+    }
+    Ok(())
+}
+
+/// Parses an optional list of variables in brackets after `defer` or `finally`.
+fn parse_captures(input: ParseStream) -> Result<()> {
+    if !input.peek(token::Paren) {
+        return Ok(());
+    }
+    let content;
+    parenthesized!(content in input);
+    while !content.is_empty() {
+        expect::<Ident>(&content, "expected the name of a variable")?;
+        if content.is_empty() {
+            break;
+        }
+        let comma = expect::<Token![,]>(&content, "expected `,` between variable names")?;
+        if content.is_empty() {
+            return Err(Error::new(
+                comma.span,
+                "unexpected trailing `,` in the list of variables",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses the `else` value or block after the second block of a do-while-do loop.
+fn parse_else(input: ParseStream) -> Result<()> {
+    if input.peek2(token::Brace) {
+        // A block is an `else` block unless it is the start of a value that ends the invocation
+        let fork = input.fork();
+        fork.parse::<Token![else]>()?;
+        if fork.parse::<Expr>().is_err() || !fork.is_empty() {
+            input.parse::<Token![else]>()?;
+            input.parse::<TokenTree>()?;
+            return Ok(());
+        }
+    }
+    parse_else_value(input)
+}
+
+/// Parses an `else` value, which has to end the invocation.
+fn parse_else_value(input: ParseStream) -> Result<()> {
+    let else_token: Token![else] = input.parse()?;
+    if input.is_empty() {
+        return Err(Error::new(else_token.span, "expected a value after `else`"));
+    }
+    expect::<Expr>(input, "expected a value after `else`")?;
+    if !input.is_empty() {
+        return Err(Error::new(
+            else_token.span,
+            "a loop with an `else` value has to be the last statement of the invocation, use an \
+             `else { ... }` block after the condition instead",
+        ));
+    }
+    Ok(())
+}
+
+/// Parses a statement that isn't a loop.
+fn parse_stmt(input: ParseStream) -> Result<()> {
+    // The final expression of the invocation, which doesn't need a `;`
+    let fork = input.fork();
+    if fork.call(Attribute::parse_outer).is_ok() && fork.parse::<Expr>().is_ok() && fork.is_empty()
+    {
+        input.advance_to(&fork);
+        return Ok(());
+    }
+    input.parse::<Stmt>()?;
+    Ok(())
+}
+
+/// Skips a block, without parsing its contents.
+fn parse_block(input: ParseStream, message: &str) -> Result<()> {
+    if !input.peek(token::Brace) {
+        return Err(input.error(message));
+    }
+    input.parse::<TokenTree>()?;
+    Ok(())
+}
+
+/// Parses a `T`, replacing the error with `message` if there isn't one.
+fn expect<T: Parse>(input: ParseStream, message: &str) -> Result<T> {
+    input
+        .parse()
+        .map_err(|error| Error::new(error.span(), message))
+}
+
+/// Collects the tokens between two cursors, to give an error the span of everything in between.
+fn tokens_between(mut begin: Cursor, end: Cursor) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    while begin < end {
+        let Some((token, next)) = begin.token_tree() else {
+            break;
+        };
+        tokens.extend([token]);
+        begin = next;
+    }
+    tokens
+}
@@ -0,0 +1,7 @@
+use do_while::do_while;
+
+fn main() {
+    do_while! {
+        do {} let x = 1 while x == 1;
+    }
+}
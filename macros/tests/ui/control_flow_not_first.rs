@@ -0,0 +1,8 @@
+use do_while::do_while;
+
+fn main() {
+    do_while! {
+        let a = 0;
+        do (state = a) {} while false;
+    }
+}
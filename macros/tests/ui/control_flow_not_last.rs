@@ -0,0 +1,8 @@
+use do_while::do_while;
+
+fn main() {
+    do_while! {
+        do (state = 0) {} while false;
+        let a = 0;
+    }
+}
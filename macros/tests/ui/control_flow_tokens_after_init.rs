@@ -0,0 +1,7 @@
+use do_while::do_while;
+
+fn main() {
+    do_while! {
+        do (state = 0, 1) {} while false;
+    }
+}
@@ -0,0 +1,10 @@
+fn main() {}
+
+do_while::enable! {
+    fn count(mut x: u32) -> u32 {
+        do {
+            x += 1;
+        } while x < 10
+        x
+    }
+}
@@ -0,0 +1,12 @@
+use do_while::do_while;
+
+fn main() {
+    let mut values = [3, 8, 0, 5].into_iter();
+    let mut sum = 0;
+    do_while! {
+        do {} while let Some(value) = values.next() && value > 0, do {
+            sum += value;
+        }
+    }
+    assert_eq!(sum, 11);
+}
@@ -0,0 +1,14 @@
+use do_while::do_while;
+
+fn main() {
+    let mut x = 0;
+    do_while! {
+        do {
+            x += 1;
+        } while x < 5, max 10;
+
+        do {
+            x -= 1;
+        } while x > 0;
+    }
+}
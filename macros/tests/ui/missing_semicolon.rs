@@ -0,0 +1,8 @@
+use do_while::do_while;
+
+fn main() {
+    do_while! {
+        do {} while false
+        do {} while false;
+    }
+}
@@ -0,0 +1,7 @@
+use do_while::do_while;
+
+fn main() {
+    do_while! {
+        do (let mut i = 0; i += 1; i += 1) {} while i < 10;
+    }
+}
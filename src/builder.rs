@@ -0,0 +1,278 @@
+use crate::do_while;
+
+/// A closure-based builder for do-while loops, mirroring the [do_while] macro.
+///
+/// The body is given to [`DoWhile::new`], the condition to [`DoWhile::while_`], and the loop is
+/// started with [`DoWhile::run`]. A second block for do-while-do loops can be added with
+/// [`DoWhile::then`], and a maximum number of iterations with [`DoWhile::max_iters`]:
+/// ```rust
+/// use do_while::{DoWhile, Outcome};
+/// use std::cell::{Cell, RefCell};
+///
+/// let items = [1, 2, 3, 4];
+/// let index = Cell::new(0);
+/// let string = RefCell::new(String::new());
+///
+/// let outcome = DoWhile::new(|| {
+///     string.borrow_mut().push_str(&items[index.get()].to_string());
+///     index.set(index.get() + 1);
+/// })
+/// .while_(|| index.get() < items.len())
+/// .then(|| string.borrow_mut().push_str(", "))
+/// .run();
+///
+/// assert_eq!(outcome, Outcome::Finished { iterations: 4 });
+/// assert_eq!(string.into_inner(), "1, 2, 3, 4".to_string());
+/// ```
+///
+/// As with any set of closures, only one of them can mutably borrow a given variable, so state
+/// shared between the body and the condition usually goes in a [`Cell`](std::cell::Cell) or
+/// [`RefCell`](std::cell::RefCell).
+///
+/// Since the builder only stores the closures it is given, it can be used from generic code that
+/// takes closures as arguments:
+/// ```rust
+/// use do_while::{DoWhile, Outcome};
+///
+/// fn retry(mut attempt: impl FnMut() -> bool, attempts: usize) -> Outcome {
+///     let succeeded = std::cell::Cell::new(false);
+///     DoWhile::new(|| succeeded.set(attempt()))
+///         .while_(|| !succeeded.get())
+///         .max_iters(attempts)
+///         .run()
+/// }
+///
+/// let mut calls = 0;
+/// assert_eq!(
+///     retry(|| { calls += 1; calls == 3 }, 5),
+///     Outcome::Finished { iterations: 3 },
+/// );
+/// assert_eq!(retry(|| false, 5), Outcome::LimitReached { iterations: 5 });
+/// ```
+///
+/// Unset parts of the loop are stored as `()` and compile down to nothing, so running a
+/// `DoWhile` is as cheap as the equivalent [do_while] loop.
+#[derive(Debug, Clone, Copy)]
+#[must_use = "a `DoWhile` does nothing until `run` is called"]
+pub struct DoWhile<B, C = (), A = (), L = ()> {
+    body: B,
+    condition: C,
+    after: A,
+    limit: L,
+}
+
+/// How a loop run with [`DoWhile::run`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// The condition evaluated to `false` after the given number of iterations.
+    Finished {
+        /// The number of times the body ran.
+        iterations: usize,
+    },
+    /// The condition was still `true` after the maximum number of iterations.
+    LimitReached {
+        /// The number of times the body ran, which is the maximum number of iterations, or 1 if the
+        /// maximum was 0.
+        iterations: usize,
+    },
+}
+
+impl Outcome {
+    /// Returns the number of times the body of the loop ran.
+    pub fn iterations(&self) -> usize {
+        match *self {
+            Outcome::Finished { iterations } | Outcome::LimitReached { iterations } => iterations,
+        }
+    }
+
+    /// Returns `true` if the loop ended because its condition evaluated to `false`.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Outcome::Finished { .. })
+    }
+}
+
+impl<B: FnMut()> DoWhile<B> {
+    /// Creates a new loop with the given body.
+    pub fn new(body: B) -> Self {
+        DoWhile {
+            body,
+            condition: (),
+            after: (),
+            limit: (),
+        }
+    }
+}
+
+impl<B, A, L> DoWhile<B, (), A, L> {
+    /// Sets the condition of the loop, which is checked after every run of the body.
+    pub fn while_<C: FnMut() -> bool>(self, condition: C) -> DoWhile<B, C, A, L> {
+        DoWhile {
+            body: self.body,
+            condition,
+            after: self.after,
+            limit: self.limit,
+        }
+    }
+}
+
+impl<B, C, L> DoWhile<B, C, (), L> {
+    /// Sets a second block to run every time the condition evaluates to `true`, turning the loop
+    /// into a do-while-do loop.
+    pub fn then<A: FnMut()>(self, after: A) -> DoWhile<B, C, A, L> {
+        DoWhile {
+            body: self.body,
+            condition: self.condition,
+            after,
+            limit: self.limit,
+        }
+    }
+}
+
+impl<B, C, A> DoWhile<B, C, A, ()> {
+    /// Sets the maximum number of times the body can run. If the condition is still `true` after
+    /// that, the loop stops and returns [`Outcome::LimitReached`].
+    ///
+    /// As the body of a do-while loop always runs at least once, a limit of 0 acts like a limit of
+    /// 1.
+    pub fn max_iters(self, limit: usize) -> DoWhile<B, C, A, usize> {
+        DoWhile {
+            body: self.body,
+            condition: self.condition,
+            after: self.after,
+            limit,
+        }
+    }
+}
+
+impl<B, C, A, L> DoWhile<B, C, A, L>
+where
+    B: FnMut(),
+    C: FnMut() -> bool,
+    A: sealed::After,
+    L: sealed::Limit,
+{
+    /// Runs the loop, returning how it ended.
+    pub fn run(mut self) -> Outcome {
+        let mut iterations: usize = 0;
+        do_while! {
+            do {
+                (self.body)();
+                iterations += 1;
+            } while (self.condition)(), do {
+                if self.limit.reached(iterations) {
+                    return Outcome::LimitReached { iterations };
+                }
+                self.after.run();
+            }
+        }
+        Outcome::Finished { iterations }
+    }
+}
+
+mod sealed {
+    /// The second block of a loop, which is `()` if there is none.
+    pub trait After {
+        fn run(&mut self);
+    }
+
+    impl After for () {
+        #[inline]
+        fn run(&mut self) {}
+    }
+
+    impl<F: FnMut()> After for F {
+        #[inline]
+        fn run(&mut self) {
+            self()
+        }
+    }
+
+    /// The maximum number of iterations of a loop, which is `()` if there is none.
+    pub trait Limit {
+        fn reached(&self, iterations: usize) -> bool;
+    }
+
+    impl Limit for () {
+        #[inline]
+        fn reached(&self, _iterations: usize) -> bool {
+            false
+        }
+    }
+
+    impl Limit for usize {
+        #[inline]
+        fn reached(&self, iterations: usize) -> bool {
+            iterations >= *self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DoWhile, Outcome};
+    use std::cell::Cell;
+
+    #[test]
+    fn test_do_while() {
+        // Simple do-while loop
+        let x = Cell::new(0);
+        let outcome = DoWhile::new(|| x.set(x.get() + 1))
+            .while_(|| x.get() < 10)
+            .run();
+        assert_eq!(outcome, Outcome::Finished { iterations: 10 });
+        assert_eq!(x.get(), 10);
+
+        // The body always runs at least once
+        let mut runs = 0;
+        let outcome = DoWhile::new(|| runs += 1).while_(|| false).run();
+        assert_eq!(outcome.iterations(), 1);
+        assert!(outcome.is_finished());
+        assert_eq!(runs, 1);
+    }
+
+    #[test]
+    fn test_do_while_do() {
+        let list = [1, 2, 3, 4];
+        let index = Cell::new(0);
+        let separators = Cell::new(0);
+        let outcome = DoWhile::new(|| index.set(index.get() + 1))
+            .then(|| separators.set(separators.get() + 1))
+            .while_(|| index.get() < list.len())
+            .run();
+        assert_eq!(outcome, Outcome::Finished { iterations: 4 });
+        assert_eq!(separators.get(), 3);
+    }
+
+    #[test]
+    fn test_max_iters() {
+        // Loop reaching its limit
+        let x = Cell::new(0);
+        let separators = Cell::new(0);
+        let outcome = DoWhile::new(|| x.set(x.get() + 1))
+            .while_(|| x.get() < 10)
+            .then(|| separators.set(separators.get() + 1))
+            .max_iters(5)
+            .run();
+        assert_eq!(outcome, Outcome::LimitReached { iterations: 5 });
+        assert!(!outcome.is_finished());
+        assert_eq!(x.get(), 5);
+        assert_eq!(separators.get(), 4);
+
+        // Loop finishing exactly at its limit
+        let x = Cell::new(0);
+        let outcome = DoWhile::new(|| x.set(x.get() + 1))
+            .max_iters(10)
+            .while_(|| x.get() < 10)
+            .run();
+        assert_eq!(outcome, Outcome::Finished { iterations: 10 });
+
+        // The body still runs once with a limit of 0
+        let x = Cell::new(0);
+        let outcome = DoWhile::new(|| x.set(x.get() + 1))
+            .while_(|| x.get() < 10)
+            .max_iters(0)
+            .run();
+        assert_eq!(outcome, Outcome::LimitReached { iterations: 1 });
+        assert_eq!(x.get(), 1);
+    }
+}
@@ -0,0 +1,55 @@
+use std::error::Error;
+use std::fmt;
+
+/// The error returned by a [`do_while!`](crate::do_while) loop with a `max` clause that is still
+/// running after its maximum number of iterations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IterationLimitExceeded {
+    /// The maximum number of iterations the loop was allowed to run for.
+    pub limit: usize,
+    /// The file containing the `do_while!` invocation.
+    pub file: &'static str,
+    /// The line of the `do_while!` invocation.
+    pub line: u32,
+}
+
+impl fmt::Display for IterationLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "do_while! loop at {}:{} exceeded its limit of {} iterations",
+            self.file, self.line, self.limit
+        )
+    }
+}
+
+impl Error for IterationLimitExceeded {}
+
+/// The error returned by a [`try_do_while!`](crate::try_do_while) loop, along with the iteration
+/// it occurred in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IterationError<E> {
+    /// The zero-based index of the iteration in which the error occurred.
+    pub iteration: usize,
+    /// The error returned by the body or condition of the loop.
+    pub error: E,
+}
+
+impl<E> IterationError<E> {
+    /// Returns the error returned by the body or condition of the loop, discarding the iteration.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E> fmt::Display for IterationError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error in iteration {} of loop", self.iteration)
+    }
+}
+
+impl<E: Error + 'static> Error for IterationError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
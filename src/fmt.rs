@@ -0,0 +1,226 @@
+//! Formatting helpers built on do-while-do loops.
+
+use std::fmt;
+
+use crate::do_while;
+
+/// A list of items formatted with a separator between each pair of items.
+///
+/// This is the list formatting example from the [do_while] documentation, except that the items
+/// are written straight to the [`Formatter`](fmt::Formatter) instead of being converted to
+/// `String`s first:
+/// ```rust
+/// use do_while::fmt::Separated;
+///
+/// let items = vec![1, 2, 3, 4];
+/// assert_eq!(Separated::new(&items, ", ").to_string(), "1, 2, 3, 4");
+/// ```
+///
+/// A prefix, a suffix and a different separator before the last item can be added:
+/// ```rust
+/// use do_while::fmt::Separated;
+///
+/// let items = ["apples", "pears", "plums"];
+/// let list = Separated::new(&items, ", ")
+///     .prefix("[")
+///     .suffix("]")
+///     .last_separator(" and ");
+/// assert_eq!(list.to_string(), "[apples, pears and plums]");
+/// ```
+///
+/// Items are formatted with their [`Display`](fmt::Display) implementation by default, or with a
+/// closure given to [`Separated::with_formatter`]. `Separated` implements [`Debug`](fmt::Debug)
+/// with the same output as `Display`, so it can also be used in debug output.
+///
+/// The items are iterated over every time the list is formatted, so they must be
+/// [`Clone`], as is the case for references to slices and most collections.
+#[derive(Clone, Copy)]
+pub struct Separated<'a, I, F = DisplayItem> {
+    items: I,
+    separator: &'a str,
+    last_separator: Option<&'a str>,
+    prefix: &'a str,
+    suffix: &'a str,
+    format: F,
+}
+
+/// The default item formatter of [`Separated`], which uses the [`Display`](fmt::Display)
+/// implementation of the items.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DisplayItem;
+
+impl<'a, I> Separated<'a, I>
+where
+    I: IntoIterator + Clone,
+    I::Item: fmt::Display,
+{
+    /// Creates a list of `items` separated by `separator`.
+    pub fn new(items: I, separator: &'a str) -> Self {
+        Separated {
+            items,
+            separator,
+            last_separator: None,
+            prefix: "",
+            suffix: "",
+            format: DisplayItem,
+        }
+    }
+}
+
+impl<'a, I, F> Separated<'a, I, F>
+where
+    I: IntoIterator + Clone,
+    F: Fn(I::Item, &mut fmt::Formatter<'_>) -> fmt::Result,
+{
+    /// Creates a list of `items` separated by `separator`, where each item is written with
+    /// `format`:
+    /// ```rust
+    /// use do_while::fmt::Separated;
+    ///
+    /// let bytes = [0x12, 0xab, 0x05];
+    /// let hex = Separated::with_formatter(&bytes, ":", |byte, f| write!(f, "{byte:02x}"));
+    /// assert_eq!(hex.to_string(), "12:ab:05");
+    /// ```
+    pub fn with_formatter(items: I, separator: &'a str, format: F) -> Self {
+        Separated {
+            items,
+            separator,
+            last_separator: None,
+            prefix: "",
+            suffix: "",
+            format,
+        }
+    }
+}
+
+impl<'a, I, F> Separated<'a, I, F>
+where
+    I: IntoIterator + Clone,
+    F: FormatItem<I::Item>,
+{
+    /// Sets a string to write before the first item. It is written even if there are no items.
+    pub fn prefix(mut self, prefix: &'a str) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Sets a string to write after the last item. It is written even if there are no items.
+    pub fn suffix(mut self, suffix: &'a str) -> Self {
+        self.suffix = suffix;
+        self
+    }
+
+    /// Sets the separator to write between the last two items, instead of the usual separator.
+    pub fn last_separator(mut self, last_separator: &'a str) -> Self {
+        self.last_separator = Some(last_separator);
+        self
+    }
+}
+
+impl<I, F> fmt::Display for Separated<'_, I, F>
+where
+    I: IntoIterator + Clone,
+    F: FormatItem<I::Item>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.prefix)?;
+
+        // One item of lookahead past `next`, to know which separator goes before it
+        let mut items = self.items.clone().into_iter();
+        let mut current = items.next();
+        let mut next = items.next();
+        do_while! {
+            do {
+                if let Some(item) = current.take() {
+                    self.format.format(item, f)?;
+                }
+            } while next.is_some(), do {
+                let after = items.next();
+                match (self.last_separator, &after) {
+                    (Some(last_separator), None) => f.write_str(last_separator)?,
+                    _ => f.write_str(self.separator)?,
+                }
+                current = next.take();
+                next = after;
+            }
+        }
+
+        f.write_str(self.suffix)
+    }
+}
+
+impl<I, F> fmt::Debug for Separated<'_, I, F>
+where
+    I: IntoIterator + Clone,
+    F: FormatItem<I::Item>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Writes a single item of a [`Separated`] list.
+///
+/// This is implemented for [`DisplayItem`], and for closures taking an item and a
+/// [`Formatter`](fmt::Formatter).
+pub trait FormatItem<T> {
+    /// Writes `item` to `f`.
+    fn format(&self, item: T, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+impl<T: fmt::Display> FormatItem<T> for DisplayItem {
+    fn format(&self, item: T, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&item, f)
+    }
+}
+
+impl<T, F> FormatItem<T> for F
+where
+    F: Fn(T, &mut fmt::Formatter<'_>) -> fmt::Result,
+{
+    fn format(&self, item: T, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self(item, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Separated;
+
+    #[test]
+    fn test_separated() {
+        let items = [1, 2, 3, 4];
+        assert_eq!(Separated::new(&items, ", ").to_string(), "1, 2, 3, 4");
+        assert_eq!(format!("{:?}", Separated::new(&items, ", ")), "1, 2, 3, 4");
+
+        // Single item and no items
+        assert_eq!(Separated::new([1], ", ").to_string(), "1");
+        assert_eq!(Separated::new(Vec::<u32>::new(), ", ").to_string(), "");
+
+        // Prefix, suffix and last separator
+        let list = Separated::new(&items, ", ")
+            .prefix("(")
+            .suffix(")")
+            .last_separator(", and ");
+        assert_eq!(list.to_string(), "(1, 2, 3, and 4)");
+        assert_eq!(list.to_string(), "(1, 2, 3, and 4)");
+        let list = Separated::new([1, 2], ", ").last_separator(" or ");
+        assert_eq!(list.to_string(), "1 or 2");
+        let list = Separated::new([1], ", ").last_separator(" or ");
+        assert_eq!(list.to_string(), "1");
+        let list = Separated::new(Vec::<u32>::new(), ", ")
+            .prefix("[")
+            .suffix("]");
+        assert_eq!(list.to_string(), "[]");
+
+        // Closure formatter
+        let list = Separated::with_formatter(&items, " + ", |item, f| write!(f, "x{item}"));
+        assert_eq!(list.to_string(), "x1 + x2 + x3 + x4");
+
+        // Formatting options on the outer formatter are passed to the items
+        assert_eq!(
+            format!("{:03}", Separated::new(&items, "|")),
+            "001|002|003|004"
+        );
+    }
+}
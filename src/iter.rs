@@ -0,0 +1,490 @@
+//! Iterators with do-while semantics.
+
+use std::fmt;
+use std::iter::FusedIterator;
+
+/// Creates an iterator which yields `init`, then keeps yielding `step` of the last item for as
+/// long as `pred` holds for the last item.
+///
+/// Unlike [`std::iter::successors`], the first item is always yielded, and the predicate is only
+/// checked after an item has been yielded, like the condition of a do-while loop:
+/// ```rust
+/// use do_while::iter;
+///
+/// let powers: Vec<u32> = iter::do_while(1, |x| x * 2, |&x| x < 100).collect();
+/// assert_eq!(powers, [1, 2, 4, 8, 16, 32, 64, 128]);
+///
+/// // The first item is yielded even if the predicate is never true
+/// let first: Vec<u32> = iter::do_while(1000, |x| x * 2, |&x| x < 100).collect();
+/// assert_eq!(first, [1000]);
+/// ```
+///
+/// The step is only called once the predicate has held for the last item, so it never produces an
+/// item that won't be yielded:
+/// ```rust
+/// use do_while::iter;
+///
+/// // Would overflow if the step was called after yielding 255
+/// let bytes = iter::do_while(0u8, |x| x + 1, |&x| x < u8::MAX);
+/// assert_eq!(bytes.count(), 256);
+/// ```
+pub fn do_while<T, F, P>(init: T, step: F, pred: P) -> DoWhileIter<T, F, P>
+where
+    F: FnMut(&T) -> T,
+    P: FnMut(&T) -> bool,
+{
+    DoWhileIter {
+        next: Some(init),
+        step,
+        pred,
+    }
+}
+
+/// An iterator with do-while semantics.
+///
+/// This `struct` is created by the [`do_while`] function. See its documentation for more.
+#[derive(Clone)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct DoWhileIter<T, F, P> {
+    next: Option<T>,
+    step: F,
+    pred: P,
+}
+
+impl<T, F, P> Iterator for DoWhileIter<T, F, P>
+where
+    F: FnMut(&T) -> T,
+    P: FnMut(&T) -> bool,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let item = self.next.take()?;
+        if (self.pred)(&item) {
+            self.next = Some((self.step)(&item));
+        }
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.next {
+            Some(_) => (1, None),
+            None => (0, Some(0)),
+        }
+    }
+}
+
+impl<T, F, P> FusedIterator for DoWhileIter<T, F, P>
+where
+    F: FnMut(&T) -> T,
+    P: FnMut(&T) -> bool,
+{
+}
+
+impl<T: fmt::Debug, F, P> fmt::Debug for DoWhileIter<T, F, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DoWhileIter")
+            .field("next", &self.next)
+            .finish()
+    }
+}
+
+/// Extension methods for iterators with do-while semantics.
+///
+/// This trait is implemented for all iterators, and is exported at the crate root so it can be
+/// imported along with [`do_while!`](crate::do_while):
+/// ```rust
+/// use do_while::IteratorDoWhileExt;
+///
+/// let digits: String = "123abc".chars().take_while_inclusive(|c| c.is_ascii_digit()).collect();
+/// assert_eq!(digits, "123a");
+/// ```
+pub trait IteratorDoWhileExt: Iterator {
+    /// Creates an iterator that yields items while `pred` returns `true`, and then yields the
+    /// first item for which it returns `false`.
+    ///
+    /// This is like [`Iterator::take_while`], except that the item which fails the predicate is
+    /// kept, as the body of a do-while loop would run for it before the condition is checked:
+    /// ```rust
+    /// use do_while::IteratorDoWhileExt;
+    ///
+    /// let items: Vec<_> = [1, 2, 3, 4, 5].into_iter().take_while_inclusive(|&x| x < 3).collect();
+    /// assert_eq!(items, [1, 2, 3]);
+    /// ```
+    fn take_while_inclusive<P>(self, pred: P) -> TakeWhileInclusive<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(&Self::Item) -> bool,
+    {
+        TakeWhileInclusive {
+            iter: self,
+            pred,
+            done: false,
+        }
+    }
+
+    /// Creates an iterator that yields items until `pred` returns `true`, including the item for
+    /// which it does.
+    ///
+    /// This is the same as [`take_while_inclusive`](IteratorDoWhileExt::take_while_inclusive) with
+    /// the predicate inverted, like a do-until loop:
+    /// ```rust
+    /// use do_while::IteratorDoWhileExt;
+    ///
+    /// let text = "first line\nsecond line";
+    /// let line: String = text.chars().take_until_inclusive(|&c| c == '\n').collect();
+    /// assert_eq!(line, "first line\n");
+    /// ```
+    fn take_until_inclusive<P>(self, pred: P) -> TakeUntilInclusive<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(&Self::Item) -> bool,
+    {
+        TakeUntilInclusive {
+            iter: self,
+            pred,
+            done: false,
+        }
+    }
+
+    /// Creates an iterator that skips items while `pred` returns `true`, also skips the first item
+    /// for which it returns `false`, and then yields all remaining items.
+    ///
+    /// This is like [`Iterator::skip_while`], except that the item which fails the predicate is
+    /// skipped too, so it yields exactly the items not yielded by
+    /// [`take_while_inclusive`](IteratorDoWhileExt::take_while_inclusive):
+    /// ```rust
+    /// use do_while::IteratorDoWhileExt;
+    ///
+    /// let items: Vec<_> = [1, 2, 3, 4, 5].into_iter().skip_while_inclusive(|&x| x < 3).collect();
+    /// assert_eq!(items, [4, 5]);
+    /// ```
+    fn skip_while_inclusive<P>(self, pred: P) -> SkipWhileInclusive<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(&Self::Item) -> bool,
+    {
+        SkipWhileInclusive {
+            iter: self,
+            pred,
+            skipped: false,
+        }
+    }
+}
+
+impl<I: Iterator> IteratorDoWhileExt for I {}
+
+/// An iterator that yields items while a predicate holds, and then the first item for which it
+/// doesn't.
+///
+/// This `struct` is created by [`IteratorDoWhileExt::take_while_inclusive`]. See its
+/// documentation for more.
+#[derive(Clone)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct TakeWhileInclusive<I, P> {
+    iter: I,
+    pred: P,
+    done: bool,
+}
+
+impl<I, P> Iterator for TakeWhileInclusive<I, P>
+where
+    I: Iterator,
+    P: FnMut(&I::Item) -> bool,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.done {
+            return None;
+        }
+        let item = self.iter.next()?;
+        self.done = !(self.pred)(&item);
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done {
+            (0, Some(0))
+        } else {
+            let (lower, upper) = self.iter.size_hint();
+            (lower.min(1), upper)
+        }
+    }
+}
+
+impl<I, P> FusedIterator for TakeWhileInclusive<I, P>
+where
+    I: FusedIterator,
+    P: FnMut(&I::Item) -> bool,
+{
+}
+
+impl<I: fmt::Debug, P> fmt::Debug for TakeWhileInclusive<I, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TakeWhileInclusive")
+            .field("iter", &self.iter)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+/// An iterator that yields items until a predicate holds, including the item for which it does.
+///
+/// This `struct` is created by [`IteratorDoWhileExt::take_until_inclusive`]. See its
+/// documentation for more.
+#[derive(Clone)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct TakeUntilInclusive<I, P> {
+    iter: I,
+    pred: P,
+    done: bool,
+}
+
+impl<I, P> Iterator for TakeUntilInclusive<I, P>
+where
+    I: Iterator,
+    P: FnMut(&I::Item) -> bool,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.done {
+            return None;
+        }
+        let item = self.iter.next()?;
+        self.done = (self.pred)(&item);
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done {
+            (0, Some(0))
+        } else {
+            let (lower, upper) = self.iter.size_hint();
+            (lower.min(1), upper)
+        }
+    }
+}
+
+impl<I, P> FusedIterator for TakeUntilInclusive<I, P>
+where
+    I: FusedIterator,
+    P: FnMut(&I::Item) -> bool,
+{
+}
+
+impl<I: fmt::Debug, P> fmt::Debug for TakeUntilInclusive<I, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TakeUntilInclusive")
+            .field("iter", &self.iter)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+/// An iterator that skips items while a predicate holds and the first item for which it doesn't,
+/// and then yields the remaining items.
+///
+/// This `struct` is created by [`IteratorDoWhileExt::skip_while_inclusive`]. See its
+/// documentation for more.
+///
+/// If the underlying iterator is double-ended, so is this one. Taking an item from the back first
+/// skips the items at the front, so the predicate is still called in order:
+/// ```rust
+/// use do_while::IteratorDoWhileExt;
+///
+/// let mut iter = [1, 2, 3, 4, 5].into_iter().skip_while_inclusive(|&x| x < 2);
+/// assert_eq!(iter.next_back(), Some(5));
+/// assert_eq!(iter.next(), Some(3));
+/// ```
+#[derive(Clone)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct SkipWhileInclusive<I, P> {
+    iter: I,
+    pred: P,
+    skipped: bool,
+}
+
+impl<I, P> SkipWhileInclusive<I, P>
+where
+    I: Iterator,
+    P: FnMut(&I::Item) -> bool,
+{
+    fn skip(&mut self) {
+        if !self.skipped {
+            let pred = &mut self.pred;
+            // `find` leaves the iterator just past the first item that fails the predicate
+            self.iter.find(|item| !pred(item));
+            self.skipped = true;
+        }
+    }
+}
+
+impl<I, P> Iterator for SkipWhileInclusive<I, P>
+where
+    I: Iterator,
+    P: FnMut(&I::Item) -> bool,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.skip();
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.iter.size_hint();
+        if self.skipped {
+            (lower, upper)
+        } else {
+            (0, upper)
+        }
+    }
+}
+
+impl<I, P> DoubleEndedIterator for SkipWhileInclusive<I, P>
+where
+    I: DoubleEndedIterator,
+    P: FnMut(&I::Item) -> bool,
+{
+    fn next_back(&mut self) -> Option<I::Item> {
+        self.skip();
+        self.iter.next_back()
+    }
+}
+
+impl<I, P> FusedIterator for SkipWhileInclusive<I, P>
+where
+    I: FusedIterator,
+    P: FnMut(&I::Item) -> bool,
+{
+}
+
+impl<I: fmt::Debug, P> fmt::Debug for SkipWhileInclusive<I, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SkipWhileInclusive")
+            .field("iter", &self.iter)
+            .field("skipped", &self.skipped)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{do_while, IteratorDoWhileExt};
+
+    #[test]
+    fn test_do_while() {
+        let mut iter = do_while(1, |x| x + 1, |&x| x < 3);
+        assert_eq!(iter.size_hint(), (1, None));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+
+        // The predicate and step are called once for each item
+        let mut steps = 0;
+        let mut checks = 0;
+        let items: Vec<_> = do_while(
+            0,
+            |x| {
+                steps += 1;
+                x + 1
+            },
+            |&x| {
+                checks += 1;
+                x < 4
+            },
+        )
+        .collect();
+        assert_eq!(items, [0, 1, 2, 3, 4]);
+        assert_eq!(steps, 4);
+        assert_eq!(checks, 5);
+
+        // Iterator chains
+        let sum: u32 = do_while(1, |x| x * 3, |&x| x < 50)
+            .filter(|x| x % 2 == 1)
+            .sum();
+        assert_eq!(sum, 1 + 3 + 9 + 27 + 81);
+    }
+
+    #[test]
+    fn test_take_while_inclusive() {
+        let items: Vec<_> = (1..10).take_while_inclusive(|&x| x < 4).collect();
+        assert_eq!(items, [1, 2, 3, 4]);
+
+        // The first item is yielded even if it fails the predicate
+        let items: Vec<_> = (1..10).take_while_inclusive(|_| false).collect();
+        assert_eq!(items, [1]);
+
+        // All items are yielded if none fail the predicate
+        let items: Vec<_> = (1..4).take_while_inclusive(|_| true).collect();
+        assert_eq!(items, [1, 2, 3]);
+
+        let mut iter = (1..10).take_while_inclusive(|&x| x < 2);
+        assert_eq!(iter.size_hint(), (1, Some(9)));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+        assert_eq!(
+            std::iter::empty::<u32>()
+                .take_while_inclusive(|_| true)
+                .size_hint(),
+            (0, Some(0))
+        );
+    }
+
+    #[test]
+    fn test_take_until_inclusive() {
+        let items: Vec<_> = (1..10).take_until_inclusive(|&x| x == 4).collect();
+        assert_eq!(items, [1, 2, 3, 4]);
+
+        let items: Vec<_> = (1..10).take_until_inclusive(|_| true).collect();
+        assert_eq!(items, [1]);
+
+        let items: Vec<_> = (1..4).take_until_inclusive(|_| false).collect();
+        assert_eq!(items, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_skip_while_inclusive() {
+        let items: Vec<_> = (1..7).skip_while_inclusive(|&x| x < 4).collect();
+        assert_eq!(items, [5, 6]);
+
+        // The first item is skipped even if it fails the predicate
+        let items: Vec<_> = (1..4).skip_while_inclusive(|_| false).collect();
+        assert_eq!(items, [2, 3]);
+
+        let items: Vec<_> = (1..4).skip_while_inclusive(|_| true).collect();
+        assert_eq!(items, []);
+
+        // Skipping from the back
+        let items: Vec<_> = (1..7).skip_while_inclusive(|&x| x < 4).rev().collect();
+        assert_eq!(items, [6, 5]);
+        let mut calls = 0;
+        let mut iter = (1..7).skip_while_inclusive(|&x| {
+            calls += 1;
+            x < 2
+        });
+        assert_eq!(iter.size_hint(), (0, Some(6)));
+        assert_eq!(iter.next_back(), Some(6));
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next_back(), Some(5));
+        assert_eq!(iter.next(), Some(4));
+        assert_eq!(iter.next(), None);
+        assert_eq!(calls, 2);
+
+        // Taking and skipping with the same predicate splits the items
+        let items = [1, 5, 2, 8, 3];
+        let taken: Vec<_> = items.iter().take_while_inclusive(|&&x| x < 6).collect();
+        let skipped: Vec<_> = items.iter().skip_while_inclusive(|&&x| x < 6).collect();
+        assert_eq!(taken, [&1, &5, &2, &8]);
+        assert_eq!(skipped, [&3]);
+    }
+}
@@ -15,150 +15,913 @@
 //!
 //! For more advanced details and usage, see the macro-level documentation for [do_while].
 
-/// A macro allowing for clean do-while loops.
+use std::ops::ControlFlow;
+
+mod builder;
+mod error;
+pub mod fmt;
+pub mod iter;
+
+pub use builder::{DoWhile, Outcome};
+pub use error::{IterationError, IterationLimitExceeded};
+pub use iter::IteratorDoWhileExt;
+
+#[cfg_attr(not(feature = "proc-macro"), doc = include_str!("do_while.md"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __do_while {
+    () => {};
+    ($( #[$attr:meta] )* do ( $( $head:tt )* ) $body:block while $cond:expr;) => {
+            $crate::__do_while! { @control_flow [$( #[$attr] )*] [$( $head )*] $body $cond }
+    };
+    // Invocations made up only of loops ending with `;`, or only of loops with a second block,
+    // none of which have `defer` or `finally` blocks or `let` bindings. These are expanded in one
+    // step, so that the number of loops doesn't add to the recursion depth. `while` and `until`
+    // are matched as an identifier and checked by `@check`, as an `expr` fragment can't follow a
+    // choice between them.
+    ($( $( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body:block $keyword:ident $cond:expr; )+) => {
+            $( $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body [] [] [] [$keyword $cond] [] [] [] } )+
+    };
+    ($( $( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body:block $keyword:ident $cond:expr, do $after:block )+) => {
+            $( $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body [] [] [] [$keyword $cond] [] [$after] [] } )+
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? repeat $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* until $( $others:tt )*) => {
+            $crate::__do_while! { $( #[$attr] )* $( $label: )? do $( |$index| )? $( ( let $( $init_step )* ) )? $body $( defer ( $( $( $deferred ),* )? ) $defer )? $( finally ( $( $( $capture ),* )? ) $finally )? $( let $binding = $value; )* until $( $others )* }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* while $cond:expr $(, max $max:expr )?; $( else $else:expr )?) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [while $cond] [$( $max )?] [] [$( $else )?] }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body_before:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* while $cond:expr $(, max $max:expr )?, do $body_after:block $( else $else:expr )?) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body_before [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [while $cond] [$( $max )?] [$body_after] [$( $else )?] }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* until $cond:expr $(, max $max:expr )?; $( else $else:expr )?) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [until $cond] [$( $max )?] [] [$( $else )?] }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body_before:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* until $cond:expr $(, max $max:expr )?, do $body_after:block $( else $else:expr )?) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body_before [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [until $cond] [$( $max )?] [$body_after] [$( $else )?] }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body_before:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* while $cond:expr $(, max $max:expr )?, do $body_after:block else $else:block $( $others:tt )+) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body_before [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [while $cond] [$( $max )?] [$body_after] [$else] }
+            $crate::__do_while! { @not_last [$( $max )?] }
+            $crate::__do_while! { $( $others )+ }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body_before:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* until $cond:expr $(, max $max:expr )?, do $body_after:block else $else:block $( $others:tt )+) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body_before [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [until $cond] [$( $max )?] [$body_after] [$else] }
+            $crate::__do_while! { @not_last [$( $max )?] }
+            $crate::__do_while! { $( $others )+ }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* while $cond:expr $(, max $max:expr )?; $( $others:tt )+) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [while $cond] [$( $max )?] [] [] }
+            $crate::__do_while! { @not_last [$( $max )?] }
+            $crate::__do_while! { $( $others )+ }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body_before:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* while $cond:expr $(, max $max:expr )?, do $body_after:block $( $others:tt )+) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body_before [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [while $cond] [$( $max )?] [$body_after] [] }
+            $crate::__do_while! { @not_last [$( $max )?] }
+            $crate::__do_while! { $( $others )+ }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* until $cond:expr $(, max $max:expr )?; $( $others:tt )+) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [until $cond] [$( $max )?] [] [] }
+            $crate::__do_while! { @not_last [$( $max )?] }
+            $crate::__do_while! { $( $others )+ }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body_before:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* until $cond:expr $(, max $max:expr )?, do $body_after:block $( $others:tt )+) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body_before [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*] [until $cond] [$( $max )?] [$body_after] [] }
+            $crate::__do_while! { @not_last [$( $max )?] }
+            $crate::__do_while! { $( $others )+ }
+    };
+
+    // `while let` conditions, which can't be parsed as an `expr` fragment, and `else` blocks
+    // directly after the condition, as an `expr` fragment can't be followed by `else`. The
+    // condition is collected token by token until what follows it is found, unless the procedural
+    // macro has already put it in brackets after `@`.
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* while $( $rest:tt )+) => {
+            $crate::__do_while! { @else [[$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*]] [while] $( $rest )+ }
+    };
+    ($( #[$attr:meta] )* $( $label:lifetime: )? do $( |$index:ident| )? $( ( let $( $init_step:tt )* ) )? $body:block $( defer $( ( $( $deferred:ident ),* ) )? $defer:block )? $( finally $( ( $( $capture:ident ),* ) )? $finally:block )? $( let $binding:pat = $value:expr; )* until $( $rest:tt )+) => {
+            $crate::__do_while! { @else [[$( #[$attr] )*] [$( $label )?] [$( $index )?] [$( let $( $init_step )* )?] $body [$( ( $( $( $deferred ),* )? ) $defer )?] [$( ( $( $( $capture ),* )? ) $finally )?] [$( let $binding = $value; )*]] [until] $( $rest )+ }
+    };
+    (@else [$( $loop:tt )*] [$keyword:ident] @ [$( $if:tt )?] [$( $cond:tt )+] $( $rest:tt )*) => {
+            $crate::__do_while! { @else [$( $loop )*] [$keyword $( @ $if )? $( $cond )+] $( $rest )* }
+    };
+    (@else [$( $loop:tt )*] [$( $cond:tt )+] $(, max $max:expr )?; $( else $else:expr )?) => {
+            $crate::__do_while! { @loop $( $loop )* [$( $cond )+] [$( $max )?] [] [$( $else )?] }
+    };
+    (@else [$( $loop:tt )*] [$( $cond:tt )+] $(, max $max:expr )?, do $body_after:block $( else $else:expr )?) => {
+            $crate::__do_while! { @loop $( $loop )* [$( $cond )+] [$( $max )?] [$body_after] [$( $else )?] }
+    };
+    (@else [$( $loop:tt )*] [$( $cond:tt )+] $(, max $max:expr )?, do $body_after:block else $else:block $( $others:tt )+) => {
+            $crate::__do_while! { @loop $( $loop )* [$( $cond )+] [$( $max )?] [$body_after] [$else] }
+            $crate::__do_while! { @not_last [$( $max )?] }
+            $crate::__do_while! { $( $others )+ }
+    };
+    (@else [$( $loop:tt )*] [$( $cond:tt )+] $(, max $max:expr )?; $( $others:tt )+) => {
+            $crate::__do_while! { @loop $( $loop )* [$( $cond )+] [$( $max )?] [] [] }
+            $crate::__do_while! { @not_last [$( $max )?] }
+            $crate::__do_while! { $( $others )+ }
+    };
+    (@else [$( $loop:tt )*] [$( $cond:tt )+] $(, max $max:expr )?, do $body_after:block $( $others:tt )+) => {
+            $crate::__do_while! { @loop $( $loop )* [$( $cond )+] [$( $max )?] [$body_after] [] }
+            $crate::__do_while! { @not_last [$( $max )?] }
+            $crate::__do_while! { $( $others )+ }
+    };
+    (@else [$( $loop:tt )*] [$( $cond:tt )+] else $else:block) => {
+            $crate::__do_while! { @loop $( $loop )* [$( $cond )+] [] [] [$else] }
+    };
+    (@else [$( $loop:tt )*] [$( $cond:tt )+] else $else:block $( $others:tt )+) => {
+            $crate::__do_while! { @loop $( $loop )* [$( $cond )+] [] [] [$else] }
+            $crate::__do_while! { $( $others )+ }
+    };
+    (@else [$( $loop:tt )*] [$( $cond:tt )+] $next:tt $( $rest:tt )*) => {
+            $crate::__do_while! { @else [$( $loop )*] [$( $cond )+ $next] $( $rest )* }
+    };
+    (@else [$( $loop:tt )*] [$( $cond:tt )+]) => {
+            ::core::compile_error!("expected `;`, `, do { ... }` or `else { ... }` after the loop condition")
+    };
+
+    // Loops of the form `do (...) { ... } while condition;`, which are either driven by
+    // `ControlFlow` or have init and step clauses. These are told apart before the bracketed tokens
+    // are parsed, as parsing `let` as a pattern or a pattern as a statement is a hard error.
+    (@control_flow [$( #[$attr:meta] )*] [let $( $init_step:tt )*] $body:block $cond:expr) => {
+            $crate::__do_while! { @loop [$( #[$attr] )*] [] [] [let $( $init_step )*] $body [] [] [] [while $cond] [] [] [] }
+    };
+    (@control_flow [$( #[$attr:meta] )*] [$state:pat_param = $init:expr] $body:block $cond:expr) => {
+            $crate::__do_while! { @attrs [$( #[$attr] )*]
+                $crate::control_flow(
+                    $init,
+                    |#[allow(unused_variables)] $state| $body,
+                    |#[allow(unused_variables)] $state| $cond,
+                )
+            }
+    };
+
+    // Expands a single loop. The condition is checked at the start of every iteration except the
+    // first, so that `continue` inside the body jumps to the condition check.
+    (@loop [$( #[$attr:meta] )*] [$( $label:lifetime )?] [$( $index:ident )?] [$( $init:stmt ; $step:expr )?] $body:block [$( ( $( $deferred:ident ),* ) $defer:block )?] [$( ( $( $capture:ident ),* ) $finally:block )?] [$( let $binding:pat = $value:expr; )*] [$( $cond:tt )*] [$( $max:expr )?] [$( $body_after:block )?] [$( $else:expr )?]) => {
+            $crate::__do_while! { @attrs [$( #[$attr] )*] {
+                $( $init )?
+                $(
+                    let mut finally = $crate::__private::Finally::new(
+                        ($( &mut $capture, )*),
+                        |($( $capture, )*)| $finally,
+                    );
+                    let ($( $capture, )*) = finally.captures();
+                    $( let $capture = &mut **$capture; )*
+                )?
+                let mut check = false;
+                $( let mut $index = $crate::__private::Counter::new(::core::stringify!($index)); )?
+                $( let mut limit = $crate::__private::Limit::new($max, ::core::file!(), ::core::line!()); )?
+                $crate::__do_while! { @limit ['limit] [$( $max )?]
+                    $( $label: )? loop {
+                        if ::core::mem::take(&mut check) {
+                            $(
+                                #[allow(unused_variables)]
+                                let $index = $index.current();
+                            )?
+                            $( $step; )?
+                            $( let $binding = $value; )*
+                            $crate::__do_while! { @check [$( $cond )*] [$( $else )?]
+                                $crate::__do_while! { @limit_check ['limit limit] [$( $max )?] }
+                                $( $body_after; )?
+                            }
+                        }
+                        check = true;
+                        $(
+                            #[allow(unused_variables)]
+                            let $index = $index.advance();
+                        )?
+                        $(
+                            let mut defer = $crate::__private::Finally::new(
+                                ($( &mut $deferred, )*),
+                                |($( $deferred, )*)| $defer,
+                            );
+                            let ($( $deferred, )*) = defer.captures();
+                            $( let $deferred = &mut **$deferred; )*
+                        )?
+                        $body;
+                    }
+                }
+            } }
+    };
+
+    // Applies the attributes given before a loop to its expansion. Doc comments are accepted as a
+    // way to describe a loop, but aren't used by rustdoc.
+    (@attrs [] $( $loop:tt )*) => {
+            $( $loop )*
+    };
+    (@attrs [$( #[$attr:meta] )+] $( $loop:tt )*) => {
+            #[allow(unused_doc_comments)]
+            $( #[$attr] )+
+            $( $loop )*
+    };
+
+    // Wraps a loop with an iteration limit in a block that evaluates to `Ok` with the value of the
+    // loop, or `Err` if the limit is exceeded.
+    (@limit [$limit_label:lifetime] [] $( $loop:tt )*) => {
+            $( $loop )*
+    };
+    (@limit [$limit_label:lifetime] [$max:expr] $( $loop:tt )*) => {
+            $limit_label: {
+                ::core::result::Result::Ok($( $loop )*)
+            }
+    };
+
+    // Counts a completed iteration against the iteration limit, and exits the wrapping block if the
+    // limit has been reached.
+    (@limit_check [$limit_label:lifetime $limit:ident] []) => {};
+    (@limit_check [$limit_label:lifetime $limit:ident] [$max:expr]) => {
+            if let ::core::result::Result::Err(error) = $limit.advance() {
+                break $limit_label ::core::result::Result::Err(error);
+            }
+    };
+
+    // A loop with a `max` clause evaluates to a `Result`, so it can't be followed by anything.
+    (@not_last []) => {};
+    (@not_last [$max:expr]) => {
+            ::core::compile_error!("a loop with a `max` clause evaluates to a `Result`, so it has to be the last statement")
+    };
+
+    // Exits the loop (with the `else` value, if there is one) once the condition says so, and
+    // otherwise runs the rest of the condition check. A `while let` condition goes in an `if`,
+    // which is the one given by the procedural macro after `@` if there is one, so that let chains
+    // are allowed in crates on the 2024 edition. An `if` written by this macro only allows them on
+    // the edition of this crate.
+    (@check [while @ $if:tt $( $cond:tt )+] [$( $else:expr )?] $( $then:tt )*) => {
+            $if $( $cond )+ {
+                $( $then )*
+            } else {
+                break $( $else )?;
+            }
+    };
+    (@check [while $cond:expr] [$( $else:expr )?] $( $then:tt )*) => {
+            if !$cond {
+                break $( $else )?;
+            }
+            $( $then )*
+    };
+    (@check [until $cond:expr] [$( $else:expr )?] $( $then:tt )*) => {
+            if $cond {
+                break $( $else )?;
+            }
+            $( $then )*
+    };
+    (@check [while let $( $cond:tt )+] [$( $else:expr )?] $( $then:tt )*) => {
+            if let $( $cond )+ {
+                $( $then )*
+            } else {
+                break $( $else )?;
+            }
+    };
+    (@check [$keyword:ident $( $cond:tt )*] [$( $else:expr )?] $( $then:tt )*) => {
+            ::core::compile_error!("expected `while` or `until` after the loop body")
+    };
+
+    // Passes a statement that isn't a loop through unchanged. Statements are told apart by their
+    // first tokens, then `let` and expression statements are parsed as a whole fragment so that
+    // the length of a statement doesn't add to the recursion depth.
+    (@stmt [$( $attrs:tt )*] # [ $( $attr:tt )* ] $( $rest:tt )*) => {
+            $crate::__do_while! { @stmt [$( $attrs )* #[$( $attr )*]] $( $rest )* }
+    };
+    (@stmt [$( $attrs:tt )*] let $( $rest:tt )*) => {
+            $crate::__do_while! { @let $( $attrs )* let $( $rest )* }
+    };
+    (@stmt [$( $attrs:tt )*] $keyword:ident $( $rest:tt )*) => {
+            $crate::__do_while! { @keyword [$( $attrs )*] $keyword $( $rest )* }
+    };
+    (@stmt [$( $attrs:tt )*] $label:lifetime: $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $attrs )* $label:] $( $rest )* }
+    };
+    (@stmt [$( $attrs:tt )*] { $( $block:tt )* } $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $attrs )*] { $( $block )* } $( $rest )* }
+    };
+    (@stmt [$( $attrs:tt )*] $( $rest:tt )*) => {
+            $crate::__do_while! { @semi [$( $attrs )*] $( $rest )* }
+    };
+
+    // Block-like expression statements, which end at their last block unless it is followed by a
+    // method call, a field or `?`.
+    (@keyword [$( $attrs:tt )*] if $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $attrs )* if] $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] match $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $attrs )* match] $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] for $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $attrs )* for] $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] while $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $attrs )* while] $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] loop $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $attrs )* loop] $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] unsafe $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $attrs )* unsafe] $( $rest )* }
+    };
+    // Items, some of which end with a block rather than `;`.
+    (@keyword [$( $attrs:tt )*] fn $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* fn $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] struct $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* struct $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] enum $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* enum $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] impl $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* impl $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] trait $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* trait $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] mod $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* mod $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] const $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* const $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] extern $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* extern $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] pub $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* pub $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] use $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* use $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] static $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* static $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] type $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* type $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] async fn $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* async fn $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] async unsafe $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* async unsafe $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] macro_rules $( $rest:tt )*) => {
+            $crate::__do_while! { @item $( $attrs )* macro_rules $( $rest )* }
+    };
+    // Macro calls with braces, which don't need a `;`.
+    (@keyword [$( $attrs:tt )*] $name:ident ! { $( $body:tt )* } ; $( $rest:tt )*) => {
+            $( $attrs )* $name! { $( $body )* };
+            $crate::__do_while! { $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] $name:ident ! { $( $body:tt )* } $( $rest:tt )*) => {
+            $( $attrs )* $name! { $( $body )* }
+            $crate::__do_while! { $( $rest )* }
+    };
+    (@keyword [$( $attrs:tt )*] $( $rest:tt )*) => {
+            $crate::__do_while! { @semi [$( $attrs )*] $( $rest )* }
+    };
+
+    (@block [$( $stmt:tt )*] { $( $block:tt )* } else $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $stmt )* { $( $block )* } else] $( $rest )* }
+    };
+    (@block [$( $stmt:tt )*] { $( $block:tt )* } . $( $rest:tt )*) => {
+            $crate::__do_while! { @tail [$( $stmt )* { $( $block )* } .] $( $rest )* }
+    };
+    (@block [$( $stmt:tt )*] { $( $block:tt )* } ? $( $rest:tt )*) => {
+            $crate::__do_while! { @tail [$( $stmt )* { $( $block )* } ?] $( $rest )* }
+    };
+    (@block [$( $stmt:tt )*] { $( $block:tt )* } ; $( $rest:tt )*) => {
+            $( $stmt )* { $( $block )* };
+            $crate::__do_while! { $( $rest )* }
+    };
+    (@block [$( $stmt:tt )*] { $( $block:tt )* }) => {
+            $( $stmt )* { $( $block )* }
+    };
+    (@block [$( $stmt:tt )*] { $( $block:tt )* } $( $rest:tt )+) => {
+            $( $stmt )* { $( $block )* }
+            $crate::__do_while! { $( $rest )+ }
+    };
+    (@block [$( $stmt:tt )*] $next:tt $( $rest:tt )*) => {
+            $crate::__do_while! { @block [$( $stmt )* $next] $( $rest )* }
+    };
+    (@block [$( $stmt:tt )*]) => {
+            $( $stmt )*
+    };
+
+    // Block-like expressions followed by a method call, field or `?`, which make up an expression
+    // statement ending with `;`.
+    (@tail [$( $stmt:tt )*] ; $( $rest:tt )*) => {
+            $( $stmt )*;
+            $crate::__do_while! { $( $rest )* }
+    };
+    (@tail [$( $stmt:tt )*] $next:tt $( $rest:tt )*) => {
+            $crate::__do_while! { @tail [$( $stmt )* $next] $( $rest )* }
+    };
+    (@tail [$( $stmt:tt )*]) => {
+            $( $stmt )*
+    };
+
+    (@item $item:item $( $rest:tt )*) => {
+            $item
+            $crate::__do_while! { $( $rest )* }
+    };
+
+    // A `stmt` fragment holding a `let` already ends with its semicolon.
+    (@let $stmt:stmt ; $( $rest:tt )*) => {
+            $stmt
+            $crate::__do_while! { $( $rest )* }
+    };
+
+    // Statements ending with `;`, and the final expression of the invocation.
+    (@semi [$( $attrs:tt )*] $expr:expr ; $( $rest:tt )*) => {
+            $( $attrs )* $expr;
+            $crate::__do_while! { $( $rest )* }
+    };
+    (@semi [$( $attrs:tt )*] $expr:expr) => {
+            $( $attrs )* $expr
+    };
+
+    // Statements that aren't loops. Expression and `let` statements are split off in one step;
+    // statements starting with attributes or a block-like expression go through `@stmt`, as an
+    // expression fragment would run on past the end of their block, and so do items starting with
+    // `async` or `macro_rules`, which an expression fragment would fail to parse.
+    (# $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] # $( $rest )+ }
+    };
+    ($label:lifetime: $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] $label: $( $rest )+ }
+    };
+    ({ $( $block:tt )* } $( $rest:tt )*) => {
+            $crate::__do_while! { @stmt [] { $( $block )* } $( $rest )* }
+    };
+    (if $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] if $( $rest )+ }
+    };
+    (match $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] match $( $rest )+ }
+    };
+    (for $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] for $( $rest )+ }
+    };
+    (while $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] while $( $rest )+ }
+    };
+    (loop $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] loop $( $rest )+ }
+    };
+    (unsafe $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] unsafe $( $rest )+ }
+    };
+    (const $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] const $( $rest )+ }
+    };
+    (static $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] static $( $rest )+ }
+    };
+    (async $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] async $( $rest )+ }
+    };
+    (macro_rules $( $rest:tt )+) => {
+            $crate::__do_while! { @stmt [] macro_rules $( $rest )+ }
+    };
+    ($name:ident ! { $( $body:tt )* } $( $rest:tt )*) => {
+            $crate::__do_while! { @keyword [] $name! { $( $body )* } $( $rest )* }
+    };
+    ($expr:expr ; $( $rest:tt )*) => {
+            $expr;
+            $crate::__do_while! { $( $rest )* }
+    };
+    ($expr:expr) => {
+            $expr
+    };
+    ($stmt:stmt ; $( $rest:tt )*) => {
+            $stmt
+            $crate::__do_while! { $( $rest )* }
+    };
+    ($( $stmt:tt )+) => {
+            $crate::__do_while! { @stmt [] $( $stmt )+ }
+    };
+}
+
+// With the `proc-macro` feature, invocations are first checked by the procedural macro, which is
+// given `$crate` to hand them back to `__do_while` with. `enable!` passes it on in the same way.
+#[cfg(feature = "proc-macro")]
+#[doc = include_str!("do_while.md")]
+#[macro_export]
+macro_rules! __do_while_checked {
+    ($( $tokens:tt )*) => {
+            $crate::__private::check! { [$crate] $( $tokens )* }
+    };
+}
+
+#[cfg(not(feature = "proc-macro"))]
+#[doc(inline)]
+pub use __do_while as do_while;
+#[cfg(feature = "proc-macro")]
+#[doc(inline)]
+pub use __do_while_checked as do_while;
+
+/// Enables do-while loops written directly in the bodies of the functions it is wrapped around,
+/// without wrapping each group of loops in `do_while!`.
 ///
-/// The basic syntax is:
+/// Every statement starting with `do` (or with a loop label and `do`) is parsed as a loop, with the
+/// same syntax as in `do_while!`, and expanded in the same way. This includes loops nested inside
+/// other blocks and inside other loops, but not loops in the arguments of macro calls:
 /// ```rust
-/// use do_while::do_while;
-/// # let condition = false;
-/// # fn do_stuff() {}
-///
-/// do_while! {
-///     do {
-///         do_stuff();
-///     } while condition;
+/// do_while::enable! {
+///     fn collatz_steps(mut n: u64) -> u32 {
+///         let mut steps = 0;
+///         do {
+///             n = if n % 2 == 0 { n / 2 } else { 3 * n + 1 };
+///             steps += 1;
+///         } while n != 1;
+///         steps
+///     }
 /// }
-/// ```
 ///
-/// This expands to:
-/// ```rust
-/// # let condition = false;
-/// # fn do_stuff() {}
-/// while {
-///     do_stuff();
-///     condition
-/// } {}
+/// assert_eq!(collatz_steps(6), 8);
 /// ```
-/// The body of the do-while loop is placed inside the condition expression of the while loop,
-/// meaning it runs before the condition is evaluated, and the actual body of the while loop is left
-/// empty. The `do_while` macro allows for this to be expressed in a cleaner and more obvious fashion.
 ///
-/// 'Do-while-do' loops, with code in _both_ the condition and the body of the expanded while loop,
-/// are also possible.
-/// ```rust
-/// use do_while::do_while;
-/// # let condition = false;
-/// # fn do_stuff() {}
-/// # fn do_more_stuff() {}
+/// This can't be an attribute, as rustc has to parse an item before passing it to an attribute
+/// macro, and `do` is a reserved keyword. Unlike an attribute, `enable!` adds a level of
+/// indentation around the functions, and rustfmt leaves everything inside its braces unformatted.
 ///
-/// do_while! {
-///     do {
-///         do_stuff();
-///     } while condition, do {
-///         do_more_stuff();
-///     }
-/// }
-/// ```
-/// This expands to:
+/// Each loop ends with its `;`, its second block or its `else` block, so `else` values can't be
+/// used. The tokens of the loops keep their spans, so errors point at the code inside the
+/// functions.
+#[cfg(feature = "proc-macro")]
+#[macro_export]
+macro_rules! enable {
+    ($( $tokens:tt )*) => {
+            $crate::__private::enable! { [$crate] $( $tokens )* }
+    };
+}
+
+/// A fallible variant of [do_while], for loops whose body or condition can fail.
+///
+/// The body, the condition and the second block of a do-while-do loop are run as fallible
+/// expressions, so `?` can be used inside them. Instead of returning from the enclosing function,
+/// the first error ends the loop, and the `try_do_while!` invocation evaluates to an
+/// [`IterationError`] holding the error and the zero-based index of the iteration it occurred in.
+/// The condition can either be a `bool` or a `Result<bool, E>`:
 /// ```rust
-/// # let condition = false;
-/// # fn do_stuff() {}
-/// # fn do_more_stuff() {}
-/// while {
-///     do_stuff();
-///     condition
-/// } {
-///     do_more_stuff();
-/// }
-/// ```
-/// Multiple loops within the same macro invocation are also possible.
+/// use do_while::{try_do_while, IterationError};
+/// use std::num::ParseIntError;
 ///
-/// ## Examples
+/// let input = ["4", "8", "x", "16"];
+/// let mut sum = 0;
 ///
-/// Simple do-while loop:
-/// ```rust
-/// use do_while::do_while;
+/// let result: Result<(), IterationError<ParseIntError>> = try_do_while! {
+///     do |i| {
+///         sum += input[i].parse::<i32>()?;
+///     } while i + 1 < input.len();
+/// };
 ///
-/// let mut x = 0;
-/// do_while! {
-///     do {
-///         x += 1;
-///     } while x < 10;
-/// }
-/// assert_eq!(x, 10);
+/// let error = result.unwrap_err();
+/// assert_eq!(error.iteration, 2);
+/// assert_eq!(sum, 12);
 /// ```
 ///
-/// Custom list formatting with a do-while-do loop:
+/// Loops use the same syntax as [do_while], with `while` or `until` conditions, an optional
+/// iteration counter, a second `do` block and an `else` value. If the condition ends the loop,
+/// `try_do_while!` evaluates to `Ok` with the `else` value (or `()` if there is none):
 /// ```rust
-/// use do_while::do_while;
+/// use do_while::{try_do_while, IterationError};
 ///
-/// let items = vec![1, 2, 3, 4];
-/// let mut string = String::new();
-///
-/// let mut index: usize = 0;
-/// do_while! {
-///     do {
-///         string.push_str(&items[index].to_string());
-///         index += 1;
-///     } while index < items.len(), do {
-///         string.push_str(", ");
+/// fn check(value: u32) -> Result<bool, String> {
+///     if value > 100 {
+///         Err(format!("{} is too large", value))
+///     } else {
+///         Ok(value < 50)
 ///     }
 /// }
 ///
-/// assert_eq!(string, "1, 2, 3, 4".to_string());
+/// let mut value = 1;
+/// let mut steps = Vec::new();
+/// let result: Result<u32, IterationError<String>> = try_do_while! {
+///     do {
+///         value *= 3;
+///     } while check(value), do {
+///         steps.push(value);
+///     } else value
+/// };
+///
+/// assert_eq!(result, Ok(81));
+/// assert_eq!(steps, [3, 9, 27]);
 /// ```
 ///
-/// Multiple loops at once:
-/// Multiple do-while and do-while-do loops can be mixed and matched in the same macro invocation:
+/// Because the body runs inside a closure in order to catch errors, `break` and `continue` cannot
+/// be used inside it. `return` can, but it returns from that closure rather than from the
+/// enclosing function: `return Ok(())` only skips the rest of the current pass of the body, like
+/// `continue` in [do_while], and `return Err(error)` ends the loop with that error just like `?`.
+/// The error type usually has to be given with a type annotation on the result, as `?` converts
+/// errors with [`From`].
+#[macro_export]
+macro_rules! try_do_while {
+    (do $( |$index:ident| )? $body:block while $cond:expr; $( else $else:expr )?) => {
+            $crate::try_do_while! { @loop [$( $index )?] $body [while $cond] [] [$( $else )?] }
+    };
+    (do $( |$index:ident| )? $body:block until $cond:expr; $( else $else:expr )?) => {
+            $crate::try_do_while! { @loop [$( $index )?] $body [until $cond] [] [$( $else )?] }
+    };
+    (do $( |$index:ident| )? $body_before:block while $cond:expr, do $body_after:block $( else $else:expr )?) => {
+            $crate::try_do_while! { @loop [$( $index )?] $body_before [while $cond] [$body_after] [$( $else )?] }
+    };
+    (do $( |$index:ident| )? $body_before:block until $cond:expr, do $body_after:block $( else $else:expr )?) => {
+            $crate::try_do_while! { @loop [$( $index )?] $body_before [until $cond] [$body_after] [$( $else )?] }
+    };
+
+    (@loop [$( $index:ident )?] $body:block [$kw:ident $cond:expr] [$( $body_after:block )?] [$( $else:expr )?]) => {
+            {
+                let mut counter = $crate::__private::Counter::new("iteration");
+                loop {
+                    let iteration = counter.advance();
+                    $(
+                        #[allow(unused_variables)]
+                        let $index = iteration;
+                    )?
+                    if let ::core::result::Result::Err(error) = $crate::__private::catch(|| {
+                        $body;
+                        ::core::result::Result::Ok(())
+                    }) {
+                        break ::core::result::Result::Err($crate::IterationError { iteration, error });
+                    }
+                    match $crate::__private::catch(|| $crate::__private::TryCondition::try_condition($cond)) {
+                        ::core::result::Result::Ok(condition) => {
+                            if $crate::try_do_while!(@stop $kw condition) {
+                                break ::core::result::Result::Ok({ $( $else )? });
+                            }
+                        }
+                        ::core::result::Result::Err(error) => {
+                            break ::core::result::Result::Err($crate::IterationError { iteration, error });
+                        }
+                    }
+                    $(
+                        if let ::core::result::Result::Err(error) = $crate::__private::catch(|| {
+                            $body_after;
+                            ::core::result::Result::Ok(())
+                        }) {
+                            break ::core::result::Result::Err($crate::IterationError { iteration, error });
+                        }
+                    )?
+                }
+            }
+    };
+
+    // Whether the loop should stop, given the result of the condition.
+    (@stop while $condition:ident) => {
+            !$condition
+    };
+    (@stop until $condition:ident) => {
+            $condition
+    };
+}
+
+/// Runs a do-while loop driven by [`ControlFlow`].
+///
+/// Starting from `init`, `body` is called with the current state. If it returns
+/// [`ControlFlow::Break`], the loop ends and the break value is returned as `Ok`. If it returns
+/// [`ControlFlow::Continue`], the new state is passed to `condition`, and the loop either runs
+/// again with the new state or, if the condition is `false`, ends and returns the final state as
+/// `Err`:
 /// ```rust
-/// use do_while::do_while;
+/// use std::ops::ControlFlow;
 ///
-/// let mut x = 0;
-/// let mut y = 0;
+/// // Find the first power of two with more than three decimal digits, giving up past 500.
+/// let result = do_while::control_flow(
+///     1u32,
+///     |n| {
+///         if n.to_string().len() > 3 {
+///             ControlFlow::Break(n)
+///         } else {
+///             ControlFlow::Continue(n * 2)
+///         }
+///     },
+///     |&n| n <= 500,
+/// );
+/// assert_eq!(result, Err(512));
+/// ```
 ///
-/// let list = vec![5, 6, 7, 8];
-/// let mut string = String::new();
-/// let mut index: usize = 0;
+/// The same loop can be written with [do_while] by giving the state and its initial value in
+/// brackets after `do`. Inside the condition, the state is a reference:
+/// ```rust
+/// use do_while::do_while;
+/// use std::ops::ControlFlow;
 ///
-/// do_while! {
-///     do {
-///         x += 1;
-///     } while x < 10;
+/// let result = do_while! {
+///     do (n = 1u32) {
+///         if n.to_string().len() > 3 {
+///             ControlFlow::Break(n)
+///         } else {
+///             ControlFlow::Continue(n * 2)
+///         }
+///     } while *n <= 5000;
+/// };
+/// assert_eq!(result, Ok(1024));
+/// ```
+pub fn control_flow<S, B, F, P>(init: S, mut body: F, mut condition: P) -> Result<B, S>
+where
+    F: FnMut(S) -> ControlFlow<B, S>,
+    P: FnMut(&S) -> bool,
+{
+    let mut state = init;
+    loop {
+        match body(state) {
+            ControlFlow::Break(value) => return Ok(value),
+            ControlFlow::Continue(next) if condition(&next) => state = next,
+            ControlFlow::Continue(next) => return Err(next),
+        }
+    }
+}
+
+/// Calls `f` on every item of `iter`, and `separator` between each pair of items.
 ///
-///     do {
-///         y -= 1;
-///     } while y > -20;
+/// This is the do-while-do loop from the [do_while] documentation, without any index bookkeeping.
+/// `separator` is called one fewer time than `f`, and neither is called if `iter` is empty:
+/// ```rust
+/// // Prints "1, 2, 3, 4"
+/// do_while::for_each_separated([1, 2, 3, 4], |item| print!("{item}"), || print!(", "));
+/// ```
 ///
-///     do {
-///         string.push_str(&list[index].to_string());
-///         index += 1;
-///     } while index < list.len(), do {
-///         string.push_str(", ");
-///     }
-/// }
+/// Since the two closures exist at the same time, they can't both capture the same variable
+/// mutably. Shared state, such as the string being built, can be put in a
+/// [`RefCell`](std::cell::RefCell):
+/// ```rust
+/// use std::cell::RefCell;
 ///
-/// assert_eq!(x, 10);
-/// assert_eq!(y, -20);
-/// assert_eq!(string, "5, 6, 7, 8".to_string());
+/// let string = RefCell::new(String::new());
+/// do_while::for_each_separated(
+///     [1, 2, 3, 4],
+///     |item| string.borrow_mut().push_str(&item.to_string()),
+///     || string.borrow_mut().push_str(", "),
+/// );
+/// assert_eq!(string.into_inner(), "1, 2, 3, 4".to_string());
 /// ```
-#[macro_export]
-macro_rules! do_while {
-    () => {};
-    (do $body:block while $cond:expr; $( $others:tt )*) => {
-            while { $body; $cond } {}
-            do_while! { $( $others )* }
-    };
-    (do $body_before:block while $cond:expr, do $body_after:block $( $others:tt )*) => {
-            while { $body_before; $cond } { $body_after; }
-            do_while! { $( $others )* }
+pub fn for_each_separated<I, F, S>(iter: I, mut f: F, mut separator: S)
+where
+    I: IntoIterator,
+    F: FnMut(I::Item),
+    S: FnMut(),
+{
+    let mut iter = iter.into_iter();
+    let Some(first) = iter.next() else {
+        return;
     };
+    f(first);
+    for item in iter {
+        separator();
+        f(item);
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    use crate::IterationLimitExceeded;
+
+    #[cfg(feature = "proc-macro")]
+    pub use do_while_macros::{check, enable};
+
+    /// Iteration counter for loops written as `do |i| { ... }`.
+    pub struct Counter {
+        pub(crate) name: &'static str,
+        pub(crate) current: Option<usize>,
+    }
+
+    impl Counter {
+        #[inline]
+        pub fn new(name: &'static str) -> Self {
+            Self {
+                name,
+                current: None,
+            }
+        }
+
+        /// Returns the index of the iteration that just finished.
+        #[inline]
+        pub fn current(&self) -> usize {
+            self.current.unwrap_or(0)
+        }
+
+        /// Advances to the next iteration and returns its index.
+        #[inline]
+        #[track_caller]
+        pub fn advance(&mut self) -> usize {
+            let next = match self.current {
+                None => 0,
+                Some(current) if cfg!(debug_assertions) => match current.checked_add(1) {
+                    Some(next) => next,
+                    None => panic!(
+                        "do_while! iteration counter `{}` overflowed `usize`",
+                        self.name
+                    ),
+                },
+                Some(current) => current.wrapping_add(1),
+            };
+            self.current = Some(next);
+            next
+        }
+    }
+
+    /// Runs a fallible part of a `try_do_while!` loop, catching any error returned with `?`.
+    #[inline]
+    pub fn catch<T, E>(f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        f()
+    }
+
+    /// Conditions accepted by `try_do_while!`.
+    pub trait TryCondition<E> {
+        fn try_condition(self) -> Result<bool, E>;
+    }
+
+    impl<E> TryCondition<E> for bool {
+        #[inline]
+        fn try_condition(self) -> Result<bool, E> {
+            Ok(self)
+        }
+    }
+
+    impl<E> TryCondition<E> for Result<bool, E> {
+        #[inline]
+        fn try_condition(self) -> Result<bool, E> {
+            self
+        }
+    }
+
+    /// Drop guard running the `finally` block of a loop, or the `defer` block of an iteration, with
+    /// mutable references to the variables it captures.
+    pub struct Finally<T, F: FnOnce(T)> {
+        inner: Option<(T, F)>,
+    }
+
+    impl<T, F: FnOnce(T)> Finally<T, F> {
+        #[inline]
+        pub fn new(captures: T, f: F) -> Self {
+            Self {
+                inner: Some((captures, f)),
+            }
+        }
+
+        /// Returns the captured variables, for use inside the loop.
+        #[inline]
+        pub fn captures(&mut self) -> &mut T {
+            match &mut self.inner {
+                Some((captures, _)) => captures,
+                None => unreachable!(),
+            }
+        }
+    }
+
+    impl<T, F: FnOnce(T)> Drop for Finally<T, F> {
+        #[inline]
+        fn drop(&mut self) {
+            if let Some((captures, f)) = self.inner.take() {
+                f(captures);
+            }
+        }
+    }
+
+    /// Iteration limit for loops with a `max` clause.
+    pub struct Limit {
+        error: IterationLimitExceeded,
+        iterations: usize,
+    }
+
+    impl Limit {
+        #[inline]
+        pub fn new(limit: usize, file: &'static str, line: u32) -> Self {
+            Self {
+                error: IterationLimitExceeded { limit, file, line },
+                iterations: 0,
+            }
+        }
+
+        /// Counts a completed iteration, failing if the loop has reached its limit. The first
+        /// iteration is counted like any other, so a limit of 0 fails after it, as a limit of 1 does.
+        #[inline]
+        pub fn advance(&mut self) -> Result<(), IterationLimitExceeded> {
+            self.iterations += 1;
+            if self.iterations >= self.error.limit {
+                Err(self.error)
+            } else {
+                Ok(())
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::do_while;
 
+    // Repeats the given tokens ten times and passes them on to the next macro
+    macro_rules! ten {
+        ($next:ident! [$( $args:tt )*] $( $tokens:tt )*) => {
+            $next! {
+                $( $args )*
+                $( $tokens )* $( $tokens )* $( $tokens )* $( $tokens )* $( $tokens )*
+                $( $tokens )* $( $tokens )* $( $tokens )* $( $tokens )* $( $tokens )*
+            }
+        };
+    }
+
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_do_while() {
         // Simple do-while loop
         let mut x = 0;
@@ -212,4 +975,1231 @@ mod tests {
         assert_eq!(y, -20);
         assert_eq!(string, "5, 6, 7, 8".to_string());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_labels() {
+        // Labelled do-while loop, broken out of from a nested loop
+        let mut x = 0;
+        let mut inner_runs = 0;
+        do_while! {
+            'outer: do {
+                x += 1;
+                let mut y = 0;
+                do_while! {
+                    do {
+                        y += 1;
+                        inner_runs += 1;
+                        if x == 3 && y == 2 {
+                            break 'outer;
+                        }
+                    } while y < 5;
+                }
+            } while x < 10;
+        }
+        assert_eq!(x, 3);
+        assert_eq!(inner_runs, 12);
+
+        // Labelled do-while-do loop
+        let list = [1, 2, 3, 4];
+        let mut string = String::new();
+        let mut index: usize = 0;
+        do_while! {
+            'items: do {
+                string.push_str(&list[index].to_string());
+                index += 1;
+            } while index < list.len(), do {
+                let mut spaces = 0;
+                do_while! {
+                    do {
+                        if index == 3 {
+                            break 'items;
+                        }
+                        spaces += 1;
+                    } while spaces < 1;
+                }
+                string.push_str(", ");
+            }
+        }
+        assert_eq!(string, "1, 2, 3".to_string());
+    }
+
+    #[test]
+    fn test_break_value() {
+        // Do-while loop yielding a value through break
+        let list = [3, 8, 5, 12, 7];
+        let mut index: usize = 0;
+        let found = do_while! {
+            do {
+                if list[index] > 10 {
+                    break Some(list[index]);
+                }
+                index += 1;
+            } while index < list.len(); else None
+        };
+        assert_eq!(found, Some(12));
+        assert_eq!(index, 3);
+
+        // Do-while loop falling back to the else value
+        let mut index: usize = 0;
+        let found = do_while! {
+            do {
+                if list[index] > 20 {
+                    break Some(list[index]);
+                }
+                index += 1;
+            } while index < list.len(); else None
+        };
+        assert_eq!(found, None);
+        assert_eq!(index, list.len());
+
+        // Do-while-do loop yielding a value
+        let mut string = String::new();
+        let mut index: usize = 0;
+        let length = do_while! {
+            do {
+                string.push_str(&list[index].to_string());
+                if string.len() > 5 {
+                    break string.len();
+                }
+                index += 1;
+            } while index < list.len(), do {
+                string.push_str(", ");
+            } else 0
+        };
+        assert_eq!(string, "3, 8, 5".to_string());
+        assert_eq!(length, 7);
+
+        // Plain break in a do-while loop without a value
+        let mut x = 0;
+        do_while! {
+            do {
+                x += 1;
+                if x == 4 {
+                    break;
+                }
+            } while x < 10;
+        }
+        assert_eq!(x, 4);
+    }
+
+    #[test]
+    fn test_continue() {
+        // continue in a do-while loop jumps to the condition check
+        let mut x = 0;
+        let mut odd = 0;
+        do_while! {
+            do {
+                x += 1;
+                if x % 2 == 0 {
+                    continue;
+                }
+                odd += 1;
+            } while x < 10;
+        }
+        assert_eq!(x, 10);
+        assert_eq!(odd, 5);
+
+        // continue on the final iteration still ends the loop
+        let mut runs = 0;
+        do_while! {
+            do {
+                runs += 1;
+                continue;
+            } while runs < 3;
+        }
+        assert_eq!(runs, 3);
+
+        // continue in the first block of a do-while-do loop skips to the condition, then the
+        // second block
+        let list = [1, 2, 3, 4, 5];
+        let mut string = String::new();
+        let mut index: usize = 0;
+        do_while! {
+            do {
+                index += 1;
+                if list[index - 1] == 3 {
+                    continue;
+                }
+                string.push_str(&list[index - 1].to_string());
+            } while index < list.len(), do {
+                string.push_str(", ");
+            }
+        }
+        assert_eq!(string, "1, 2, , 4, 5".to_string());
+
+        // continue in the second block of a do-while-do loop starts the next iteration
+        let mut string = String::new();
+        let mut index: usize = 0;
+        do_while! {
+            do {
+                string.push_str(&list[index].to_string());
+                index += 1;
+            } while index < list.len(), do {
+                if index == 2 {
+                    continue;
+                }
+                string.push_str(", ");
+            }
+        }
+        assert_eq!(string, "1, 23, 4, 5".to_string());
+
+        // continue with a label from a nested loop jumps to the outer condition check
+        let mut x = 0;
+        let mut inner_runs = 0;
+        do_while! {
+            'outer: do {
+                x += 1;
+                do_while! {
+                    do {
+                        inner_runs += 1;
+                        if x > 0 {
+                            continue 'outer;
+                        }
+                    } while inner_runs < 100;
+                }
+            } while x < 5;
+        }
+        assert_eq!(x, 5);
+        assert_eq!(inner_runs, 5);
+    }
+
+    #[test]
+    fn test_else() {
+        // else block running when the condition ends the loop
+        let mut x = 0;
+        let mut finished = 0;
+        do_while! {
+            do {
+                x += 1;
+            } while x < 10 else {
+                finished += 1;
+            }
+        }
+        assert_eq!(x, 10);
+        assert_eq!(finished, 1);
+
+        // else block skipped on break
+        let mut x = 0;
+        let mut finished = false;
+        do_while! {
+            do {
+                x += 1;
+                if x == 5 {
+                    break;
+                }
+            } until x == 10 else {
+                finished = true;
+            }
+        }
+        assert_eq!(x, 5);
+        assert!(!finished);
+
+        // while let condition, with the else block giving the value of the loop
+        let mut items = [1, 3, 5, 7].into_iter();
+        let mut total = 0;
+        let result = do_while! {
+            do {
+                total += 1;
+            } while let Some(_) = items.next() else {
+                None
+            }
+        };
+        assert_eq!(result, None::<u32>);
+        assert_eq!(total, 5);
+
+        // else blocks in multiple loops, and breaking out of an outer loop
+        let list = [1, 2, 3, 4];
+        let mut index: usize = 0;
+        let mut string = String::new();
+        let mut outer_finished = false;
+        let mut inner_finished = 0;
+        do_while! {
+            'outer: do {
+                let mut y = 0;
+                do_while! {
+                    do {
+                        y += 1;
+                        if index == 2 && y == 2 {
+                            break 'outer;
+                        }
+                    } while y < 3 else {
+                        inner_finished += 1;
+                    }
+                }
+                index += 1;
+            } while index < list.len() else {
+                outer_finished = true;
+            }
+
+            do {
+                string.push_str(&list[index].to_string());
+                index += 1;
+            } while index < list.len(), do {
+                string.push_str(", ");
+            } else {
+                string.push('.');
+            }
+
+            do {
+                index -= 1;
+            } while index > 2 else {
+                string.push('!');
+            }
+        }
+        assert!(!outer_finished);
+        assert_eq!(inner_finished, 2);
+        assert_eq!(string, "3, 4.!".to_string());
+    }
+
+    #[test]
+    fn test_finally() {
+        use std::cell::Cell;
+
+        // Condition ending the loop
+        let released = Cell::new(0);
+        let mut x = 0;
+        do_while! {
+            do {
+                x += 1;
+            } finally {
+                released.set(released.get() + 1);
+            } while x < 10;
+        }
+        assert_eq!(x, 10);
+        assert_eq!(released.get(), 1);
+
+        // break, running after the else value
+        let log = std::cell::RefCell::new(Vec::new());
+        let found = do_while! {
+            do {
+                log.borrow_mut().push("body");
+                if !log.borrow().is_empty() {
+                    break true;
+                }
+            } finally {
+                log.borrow_mut().push("finally");
+            } while false; else {
+                log.borrow_mut().push("else");
+                false
+            }
+        };
+        assert!(found);
+        assert_eq!(*log.borrow(), ["body", "finally"]);
+
+        // return and ?
+        fn run(released: &Cell<usize>, fail: bool) -> Result<usize, &'static str> {
+            let mut x = 0;
+            do_while! {
+                do {
+                    x += 1;
+                    if x == 3 {
+                        if fail {
+                            Err("failed")?;
+                        }
+                        return Ok(x);
+                    }
+                } finally {
+                    released.set(released.get() + 1);
+                } while x < 10;
+            }
+            Ok(0)
+        }
+        let released = Cell::new(0);
+        assert_eq!(run(&released, false), Ok(3));
+        assert_eq!(released.get(), 1);
+        assert_eq!(run(&released, true), Err("failed"));
+        assert_eq!(released.get(), 2);
+
+        // Panic
+        let released = Cell::new(0);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut x = 0;
+            do_while! {
+                do {
+                    x += 1;
+                    if x == 5 {
+                        panic!("x is 5");
+                    }
+                } finally {
+                    released.set(released.get() + 1);
+                } while x < 10;
+            }
+        }));
+        assert!(result.is_err());
+        assert_eq!(released.get(), 1);
+
+        // Captured variables, used mutably by both the body and the finally block
+        let mut buffer = Vec::new();
+        let mut flushed = Vec::new();
+        do_while! {
+            do |i| {
+                buffer.push(i);
+                if buffer.len() == 2 {
+                    flushed.append(buffer);
+                }
+            } finally(buffer, flushed) {
+                flushed.append(buffer);
+            } while i < 4;
+        }
+        assert!(buffer.is_empty());
+        assert_eq!(flushed, [0, 1, 2, 3, 4]);
+
+        // finally blocks in labelled and multiple loops
+        let released = Cell::new(0);
+        let mut x = 0;
+        do_while! {
+            'outer: do {
+                do_while! {
+                    do {
+                        x += 1;
+                        if x == 3 {
+                            break 'outer;
+                        }
+                    } finally {
+                        released.set(released.get() + 1);
+                    } while x < 10;
+                }
+            } finally {
+                released.set(released.get() + 10);
+            } while x < 10;
+
+            do {
+                x += 1;
+            } finally {
+                released.set(released.get() + 100);
+            } while x < 5, do {
+                x += 1;
+            }
+        }
+        assert_eq!(x, 6);
+        assert_eq!(released.get(), 111);
+    }
+
+    #[test]
+    fn test_defer() {
+        use std::cell::RefCell;
+
+        // defer block running after every pass of the body, before the condition
+        let log = RefCell::new(Vec::new());
+        let mut x = 0;
+        do_while! {
+            do {
+                x += 1;
+                log.borrow_mut().push(format!("body {x}"));
+                if x == 2 {
+                    continue;
+                }
+                if x == 4 {
+                    break;
+                }
+            } defer {
+                log.borrow_mut().push("defer".to_string());
+            } finally {
+                log.borrow_mut().push("finally".to_string());
+            } while {
+                log.borrow_mut().push("condition".to_string());
+                x < 10
+            }, do {
+                log.borrow_mut().push("after".to_string());
+            }
+        }
+        assert_eq!(
+            *log.borrow(),
+            [
+                "body 1",
+                "defer",
+                "condition",
+                "after",
+                "body 2",
+                "defer",
+                "condition",
+                "after",
+                "body 3",
+                "defer",
+                "condition",
+                "after",
+                "body 4",
+                "defer",
+                "finally",
+            ]
+        );
+
+        // Captured scratch buffer, reset after every iteration
+        let mut scratch = String::new();
+        let mut words = Vec::new();
+        let mut input = ["a b", "", "c d e"].into_iter();
+        do_while! {
+            do |i| {
+                let Some(line) = input.next() else {
+                    break;
+                };
+                if line.is_empty() {
+                    continue;
+                }
+                scratch.push_str(&line.replace(' ', ""));
+                words.push(format!("{i}:{scratch}"));
+            } defer(scratch) {
+                scratch.clear();
+            } while i < 10;
+        }
+        assert!(scratch.is_empty());
+        assert_eq!(words, ["0:ab", "2:cde"]);
+
+        // defer blocks in nested loops with a labelled break
+        let deferred = RefCell::new(Vec::new());
+        do_while! {
+            'outer: do |i| {
+                do_while! {
+                    do |j| {
+                        if i == 1 && j == 1 {
+                            break 'outer;
+                        }
+                    } defer {
+                        deferred.borrow_mut().push((i, j));
+                    } while j < 2;
+                }
+            } defer {
+                deferred.borrow_mut().push((i, 99));
+            } while i < 2;
+        }
+        assert_eq!(
+            *deferred.borrow(),
+            [(0, 0), (0, 1), (0, 2), (0, 99), (1, 0), (1, 1), (1, 99)]
+        );
+    }
+
+    #[test]
+    fn test_init_step() {
+        // Do-while loop with init and step clauses, stepping on continue
+        let mut items = Vec::new();
+        do_while! {
+            do (let mut i = 0; i += 1) {
+                if i == 2 {
+                    continue;
+                }
+                items.push(i);
+            } while i < 5;
+        }
+        assert_eq!(items, [0, 1, 3, 4]);
+
+        // The body runs once before the condition is checked
+        let mut runs = 0;
+        do_while! {
+            do (let mut i = 10; i += 1) {
+                runs += 1;
+            } while i < 5;
+        }
+        assert_eq!(runs, 1);
+
+        // Do-while-do loop, with a typed init and the step running before the lets
+        let list = [1, 2, 3, 4];
+        let mut string = String::new();
+        do_while! {
+            'join: do (let mut index: usize = 0; index += 1) {
+                string.push_str(&list[index].to_string());
+            } let done = index == list.len(); while !done, do {
+                string.push_str(", ");
+                if index == 3 {
+                    break 'join;
+                }
+            }
+        }
+        assert_eq!(string, "1, 2, 3, ".to_string());
+
+        // Init and step with an iteration counter, in an invocation with several loops
+        let mut total = 0;
+        let mut squares = Vec::new();
+        do_while! {
+            do |n| (let mut x = 1; x *= 2) {
+                squares.push(n * n);
+            } until x > 8;
+
+            do (let (mut a, mut b) = (0, 1); (a, b) = (b, a + b)) {
+                total += a;
+            } while b < 10;
+        }
+        assert_eq!(squares, [0, 1, 4, 9]);
+        assert_eq!(total, 12);
+    }
+
+    #[test]
+    fn test_attributes() {
+        // cfg on one of several loops
+        let mut x = 0;
+        let mut y = 0;
+        do_while! {
+            #[cfg(any())]
+            do {
+                x += 100;
+            } while x < 10;
+
+            /// Counts y up to 5
+            #[allow(clippy::int_plus_one)]
+            'count: do {
+                y += 1;
+                if y + 1 > 5 {
+                    break 'count;
+                }
+            } while y < 10;
+
+            #[cfg(all())]
+            do {
+                x += 1;
+            } while x < 3, do {
+                x += 1;
+            }
+        }
+        assert_eq!(x, 3);
+        assert_eq!(y, 5);
+
+        // Attributes on repeat-until and ControlFlow-driven loops
+        let mut z = 0;
+        do_while! {
+            #[cfg(any())]
+            repeat {
+                z += 10;
+            } until z > 0;
+
+            #[allow(unused_mut)]
+            do (let mut i = 0; i += 1) {
+                z += i;
+            } while i < 4;
+        }
+        assert_eq!(z, 6);
+
+        #[allow(unused_must_use)]
+        {
+            do_while! {
+                #[cfg(all())]
+                do (n = 1) {
+                    z += n;
+                    std::ops::ControlFlow::<(), _>::Continue(n + 1)
+                } while *n < 3;
+            };
+        }
+        assert_eq!(z, 9);
+    }
+
+    #[test]
+    fn test_statements() {
+        fn run(limit: u32) -> (u32, Vec<String>) {
+            do_while! {
+                let mut log = Vec::new();
+                let mut x = 0;
+                #[allow(unused_mut)]
+                let mut total: u32 = 0;
+
+                do {
+                    x += 1;
+                } while x < limit;
+
+                log.push(format!("x = {x}"));
+                total += x;
+
+                if x > 3 {
+                    log.push("big".to_string());
+                } else if x > 1 {
+                    log.push("medium".to_string());
+                } else {
+                    log.push("small".to_string());
+                }
+
+                fn double(n: u32) -> u32 {
+                    n * 2
+                }
+                struct Wrapper(u32);
+
+                'outer: for i in 0..3 {
+                    do_while! {
+                        do |j| {
+                            if i + j as u32 == 3 {
+                                break 'outer;
+                            }
+                            total += 1;
+                        } while j < 1;
+                    }
+                }
+                let wrapped = Wrapper(double(total));
+                let label = match wrapped.0 {
+                    0 => "none",
+                    _ => "some",
+                };
+                log.push(label.to_string());
+                vec! { 1, 2 };
+
+                async fn ready() -> u32 {
+                    1
+                }
+                let _ready = ready();
+                macro_rules! triple {
+                    ($n:expr) => {
+                        $n * 3
+                    };
+                }
+                match x {
+                    0 => None,
+                    n => Some(triple!(n)),
+                }.into_iter().for_each(|n| log.push(format!("3x = {n}")));
+
+                do {
+                    total += 100;
+                } until total > 50;
+
+                (wrapped.0 + total, log)
+            }
+        }
+        let (result, log) = run(5);
+        assert_eq!(result, 130);
+        assert_eq!(log, ["x = 5", "big", "some", "3x = 15"]);
+        assert_eq!(run(0).1, ["x = 1", "small", "some", "3x = 3"]);
+
+        // `?` after a block-like expression
+        fn halve(n: u32) -> Option<u32> {
+            do_while! {
+                let mut half = 0;
+                match n % 2 {
+                    0 => Some(n),
+                    _ => None,
+                }?;
+                do {
+                    half += 1;
+                } while half * 2 < n;
+                Some(half)
+            }
+        }
+        assert_eq!(halve(6), Some(3));
+        assert_eq!(halve(5), None);
+    }
+
+    #[test]
+    fn test_many_statements() {
+        // 100 statements after a loop, each statement adding one level of recursion at most
+        let mut x = 0;
+        ten! {
+            ten! [do_while! [
+                do {
+                    x += 1;
+                } while x < 3;
+            ]]
+            x += 1;
+        }
+        assert_eq!(x, 103);
+
+        // A single long statement costs no more than a short one
+        do_while! {
+            let n = 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1
+                + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1
+                + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1
+                + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1;
+            do {
+                x -= 1;
+            } while x > n;
+        }
+        assert_eq!((n, x), (64, 64));
+    }
+
+    #[test]
+    fn test_many_loops() {
+        // 1,000 loops in one invocation stay well below the default recursion limit of 128
+        let mut x = 0;
+        ten! {
+            ten! [ten! [do_while! []]]
+            do {
+                x += 1;
+            } while x % 2 == 1;
+        }
+        assert_eq!(x, 2000);
+
+        // The same with do-while-do loops
+        let (mut x, mut y) = (0, 0);
+        ten! {
+            ten! [ten! [do_while! []]]
+            do {
+                x += 1;
+            } while x % 2 == 1, do {
+                y += 1;
+            }
+        }
+        assert_eq!((x, y), (2000, 1000));
+
+        // `while` and `until` loops can be mixed
+        let mut x = 0;
+        ten! {
+            ten! [ten! [do_while! []]]
+            do {
+                x += 1;
+            } while x % 2 == 1;
+            'label: do {
+                x += 1;
+                if x % 2 == 1 {
+                    continue 'label;
+                }
+            } until x % 2 == 0;
+        }
+        assert_eq!(x, 4000);
+    }
+
+    #[test]
+    fn test_bindings() {
+        // Bindings used by the condition
+        let list = [4, 7, 2, 0, 5];
+        let mut index: usize = 0;
+        do_while! {
+            do {
+                index += 1;
+            } let value = list[index - 1]; while value != 0;
+        }
+        assert_eq!(index, 4);
+
+        // Bindings used by the second block and the else value
+        let mut index: usize = 0;
+        let mut sum = 0;
+        let zero_at = do_while! {
+            do {
+                index += 1;
+            }
+            let value = list[index - 1];
+            let (position, last) = (index - 1, index == list.len());
+            while !last, do {
+                sum += value;
+                if value == 0 {
+                    break Some(position);
+                }
+            } else None
+        };
+        assert_eq!(zero_at, Some(3));
+        assert_eq!(sum, 13);
+
+        // Bindings are evaluated after continue
+        let mut index: usize = 0;
+        let mut skipped = 0;
+        do_while! {
+            do {
+                index += 1;
+                if index == 2 || index == 4 {
+                    skipped += 1;
+                    continue;
+                }
+            } let done = index >= list.len(); while !done;
+        }
+        assert_eq!(index, 5);
+        assert_eq!(skipped, 2);
+    }
+
+    #[test]
+    fn test_until() {
+        // Do-until loop
+        let mut x = 0;
+        do_while! {
+            do {
+                x += 1;
+            } until x >= 10;
+        }
+        assert_eq!(x, 10);
+
+        // Repeat-until loop runs at least once
+        let mut runs = 0;
+        do_while! {
+            repeat {
+                runs += 1;
+            } until true;
+        }
+        assert_eq!(runs, 1);
+
+        // Do-until-do loop
+        let list = [1, 2, 3, 4];
+        let mut string = String::new();
+        let mut index: usize = 0;
+        do_while! {
+            do {
+                string.push_str(&list[index].to_string());
+                index += 1;
+            } until index == list.len(), do {
+                string.push_str(", ");
+            }
+        }
+        assert_eq!(string, "1, 2, 3, 4".to_string());
+
+        // Until loops mixed with while loops, labels, bindings and else values
+        let mut x = 0;
+        let mut y = 0;
+        do_while! {
+            'outer: repeat {
+                x += 1;
+                if x == 20 {
+                    break 'outer;
+                }
+            } let done = x >= 5; until done;
+
+            do {
+                y += 1;
+            } while y < x;
+        }
+        assert_eq!(x, 5);
+        assert_eq!(y, 5);
+
+        let mut index: usize = 0;
+        let found = do_while! {
+            repeat {
+                if list[index] == 3 {
+                    break Some(index);
+                }
+                index += 1;
+            } until index == list.len(); else None
+        };
+        assert_eq!(found, Some(2));
+    }
+
+    #[test]
+    fn test_while_let() {
+        // Do-while-let loop
+        let mut stack = vec![1, 2, 3];
+        let mut popped = 0;
+        do_while! {
+            do {
+                popped += 1;
+            } while let Some(_) = stack.pop();
+        }
+        assert_eq!(popped, 4);
+        assert!(stack.is_empty());
+
+        // Do-while-let-do loop using the bound variables
+        let mut tokens = "a b c".split(' ');
+        let mut string = String::new();
+        do_while! {
+            do {
+                string.push('[');
+            } while let Some(token) = tokens.next(), do {
+                string.push_str(token);
+                string.push(']');
+            }
+        }
+        assert_eq!(string, "[a][b][c][".to_string());
+
+        // Or-patterns, with bindings and an else value
+        let list = [Ok(1), Err(2), Ok(3), Err(-1), Ok(5)];
+        let mut index: usize = 0;
+        let mut sum = 0;
+        let failed_at = do_while! {
+            do {
+                index += 1;
+            } let item = list[index - 1]; while let Ok(value) | Err(value @ 0..) = item, do {
+                sum += value;
+                if index == list.len() {
+                    break None;
+                }
+            } else Some(index - 1)
+        };
+        assert_eq!(failed_at, Some(3));
+        assert_eq!(sum, 6);
+    }
+
+    #[test]
+    fn test_iteration_counter() {
+        // Counter used in the body and the condition
+        let mut indices = Vec::new();
+        do_while! {
+            do |i| {
+                indices.push(i);
+            } while i < 4;
+        }
+        assert_eq!(indices, [0, 1, 2, 3, 4]);
+
+        // Counter used in the second block of a do-while-do loop
+        let list = [5, 6, 7, 8];
+        let mut string = String::new();
+        do_while! {
+            do |index| {
+                string.push_str(&list[index].to_string());
+            } until index == list.len() - 1, do {
+                string.push_str(&format!(" ({index}), "));
+            }
+        }
+        assert_eq!(string, "5 (0), 6 (1), 7 (2), 8".to_string());
+
+        // Counter with continue, labels, bindings and else values
+        let mut runs = 0;
+        let found = do_while! {
+            'search: repeat |i| {
+                runs += 1;
+                if i % 2 == 1 {
+                    continue 'search;
+                }
+                if list[i] == 7 {
+                    break Some(i);
+                }
+            } let done = i + 1 == list.len(); until done; else None
+        };
+        assert_eq!(found, Some(2));
+        assert_eq!(runs, 3);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "do_while! iteration counter `i` overflowed `usize`")]
+    fn test_iteration_counter_overflow() {
+        let mut counter = crate::__private::Counter {
+            name: "i",
+            current: Some(usize::MAX),
+        };
+        counter.advance();
+    }
+
+    #[test]
+    fn test_max_iterations() {
+        use crate::IterationLimitExceeded;
+
+        // Loop ending before the limit
+        let mut x = 0;
+        let result = do_while! {
+            do {
+                x += 1;
+            } while x < 10, max 10;
+        };
+        assert_eq!(result, Ok(()));
+        assert_eq!(x, 10);
+
+        // Loop exceeding the limit
+        let mut x = 0;
+        let line = line!() + 1;
+        let result = do_while! {
+            do {
+                x += 1;
+            } while x < 10, max 5;
+        };
+        assert_eq!(
+            result,
+            Err(IterationLimitExceeded {
+                limit: 5,
+                file: file!(),
+                line,
+            })
+        );
+        assert_eq!(x, 5);
+
+        // The body still runs once with a limit of 0
+        let mut x = 0;
+        let result = do_while! {
+            do {
+                x += 1;
+            } while x < 10, max 0;
+        };
+        assert!(matches!(
+            result,
+            Err(IterationLimitExceeded { limit: 0, .. })
+        ));
+        assert_eq!(x, 1);
+
+        // Do-while-do loop with a limit, break values and an else value
+        let list = [1, 2, 3, 4, 5, 6];
+        let mut string = String::new();
+        let mut index: usize = 0;
+        let result = do_while! {
+            do {
+                string.push_str(&list[index].to_string());
+                index += 1;
+            } while index < list.len(), max 4, do {
+                string.push_str(", ");
+            } else index
+        };
+        assert!(result.is_err());
+        assert_eq!(string, "1, 2, 3, 4".to_string());
+
+        let mut index: usize = 0;
+        let result = do_while! {
+            do {
+                if list[index] == 3 {
+                    break Some(index);
+                }
+                index += 1;
+            } until index == list.len(), max 100; else None
+        };
+        assert_eq!(result, Ok(Some(2)));
+
+        // Propagating the error with ?
+        fn run(limit: usize) -> Result<usize, IterationLimitExceeded> {
+            let mut x = 0;
+            do_while! {
+                do |i| {
+                    x += i;
+                } while i < 9, max limit;
+            }?;
+            Ok(x)
+        }
+        assert_eq!(run(10), Ok(45));
+        assert!(run(9).is_err());
+    }
+
+    #[test]
+    fn test_try_do_while() {
+        use crate::IterationError;
+        use std::num::ParseIntError;
+
+        // Loop ending normally
+        let input = ["1", "2", "3"];
+        let mut sum = 0;
+        let result: Result<(), IterationError<ParseIntError>> = try_do_while! {
+            do |i| {
+                sum += input[i].parse::<i32>()?;
+            } while i + 1 < input.len();
+        };
+        assert_eq!(result, Ok(()));
+        assert_eq!(sum, 6);
+
+        // Error in the body
+        let input = ["1", "2", "three", "4"];
+        let mut sum = 0;
+        let result: Result<(), IterationError<ParseIntError>> = try_do_while! {
+            do |i| {
+                sum += input[i].parse::<i32>()?;
+            } until i + 1 == input.len();
+        };
+        assert_eq!(result.unwrap_err().iteration, 2);
+        assert_eq!(sum, 3);
+
+        // Error in a condition returning Result<bool, E>
+        let mut index: usize = 0;
+        let result: Result<usize, IterationError<ParseIntError>> = try_do_while! {
+            do {
+                index += 1;
+            } while input[index].parse::<i32>().map(|value| value < 10); else index
+        };
+        assert_eq!(result.unwrap_err().iteration, 1);
+
+        // Error in the second block of a do-while-do loop, and in a condition using ?
+        let mut string = String::new();
+        let mut index: usize = 0;
+        let result: Result<(), IterationError<ParseIntError>> = try_do_while! {
+            do {
+                string.push_str(input[index]);
+                index += 1;
+            } while index < input.len(), do {
+                input[index].parse::<i32>()?;
+                string.push_str(", ");
+            }
+        };
+        let error = result.unwrap_err();
+        assert_eq!(error.iteration, 1);
+        assert_eq!(string, "1, 2".to_string());
+        // The inner error is left to `source`, so that error reporters don't print it twice
+        assert_eq!(error.to_string(), "error in iteration 1 of loop");
+        assert_eq!(
+            std::error::Error::source(&error).map(ToString::to_string),
+            Some(error.error.to_string())
+        );
+
+        let mut index: usize = 0;
+        let result: Result<i32, IterationError<ParseIntError>> = try_do_while! {
+            do {
+                index += 1;
+            } until input[index].parse::<i32>()? > 1, do {
+                index += 0;
+            } else input[index].parse::<i32>().unwrap()
+        };
+        assert_eq!(result, Ok(2));
+
+        // `return` in the body only leaves the current pass of the body
+        let mut n = 0;
+        let mut skipped = 0;
+        let result: Result<(), IterationError<String>> = try_do_while! {
+            do {
+                n += 1;
+                if n % 2 == 0 {
+                    return Ok(());
+                }
+                skipped += 1;
+            } while n < 5;
+        };
+        assert_eq!(result, Ok(()));
+        assert_eq!((n, skipped), (5, 3));
+
+        let result: Result<(), IterationError<String>> = try_do_while! {
+            do |i| {
+                if i == 2 {
+                    return Err("stopped".to_string());
+                }
+            } while true;
+        };
+        let error = result.unwrap_err();
+        assert_eq!((error.iteration, error.error.as_str()), (2, "stopped"));
+    }
+
+    #[test]
+    fn test_control_flow() {
+        use std::ops::ControlFlow;
+
+        // Loop ending with a break value
+        let mut calls = 0;
+        let result = crate::control_flow(
+            1,
+            |n| {
+                calls += 1;
+                if n > 100 {
+                    ControlFlow::Break(n)
+                } else {
+                    ControlFlow::Continue(n * 3)
+                }
+            },
+            |&n| n < 1000,
+        );
+        assert_eq!(result, Ok(243));
+        assert_eq!(calls, 6);
+
+        // Loop ending because of the condition, returning the last state
+        let result: Result<(), _> =
+            crate::control_flow(1, |n| ControlFlow::Continue(n * 3), |&n| n < 1000);
+        assert_eq!(result, Err(2187));
+
+        // The body always runs at least once
+        let result = crate::control_flow(0, |n| ControlFlow::<&str, _>::Continue(n + 1), |_| false);
+        assert_eq!(result, Err(1));
+
+        // Macro form, with a destructured state
+        let result = do_while! {
+            do ((a, b) = (0u64, 1u64)) {
+                if a > 50 {
+                    ControlFlow::Break(a)
+                } else {
+                    ControlFlow::Continue((b, a + b))
+                }
+            } while *b < 1000;
+        };
+        assert_eq!(result, Ok(55));
+
+        let result: Result<u64, _> = do_while! {
+            do ((a, b) = (0u64, 1u64)) {
+                ControlFlow::Continue((b, a + b))
+            } while *b < 100;
+        };
+        assert_eq!(result, Err((89, 144)));
+    }
+
+    #[test]
+    fn test_for_each_separated() {
+        let parts = std::cell::RefCell::new(Vec::new());
+        crate::for_each_separated(
+            [1, 2, 3],
+            |item| parts.borrow_mut().push(item.to_string()),
+            || parts.borrow_mut().push(",".to_string()),
+        );
+        assert_eq!(parts.into_inner(), ["1", ",", "2", ",", "3"]);
+
+        // Single item
+        let mut items = Vec::new();
+        let mut separators = 0;
+        crate::for_each_separated(Some(1), |item| items.push(item), || separators += 1);
+        assert_eq!(items, [1]);
+        assert_eq!(separators, 0);
+
+        // Empty iterator
+        let mut items = Vec::new();
+        let mut separators = 0;
+        crate::for_each_separated(
+            Vec::<u32>::new(),
+            |item| items.push(item),
+            || separators += 1,
+        );
+        assert!(items.is_empty());
+        assert_eq!(separators, 0);
+    }
+}
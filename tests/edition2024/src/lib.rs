@@ -0,0 +1,43 @@
+use do_while::do_while;
+
+/// Adds up the values before the first one that isn't positive.
+pub fn sum_positive_prefix(values: &[i32]) -> i32 {
+    let mut values = values.iter().copied();
+    let mut sum = 0;
+    do_while! {
+        do {} while let Some(value) = values.next() && value > 0, do {
+            sum += value;
+        }
+    }
+    sum
+}
+
+/// Parses the words of `words` up to the first one that isn't a number, and returns the numbers
+/// along with whether all of the words were parsed.
+pub fn parse_leading(words: &[&str]) -> (Vec<u32>, bool) {
+    let mut words = words.iter();
+    let mut numbers = Vec::new();
+    let all;
+    do_while! {
+        let mut word = words.next();
+        do {} while let Some(next) = word && let Ok(number) = next.parse(), do {
+            numbers.push(number);
+            word = words.next();
+        } else {
+            all = word.is_none();
+        }
+    }
+    (numbers, all)
+}
+
+do_while::enable! {
+    /// Returns the index of the first value that isn't positive, or the length of `values` if they
+    /// all are.
+    pub fn first_non_positive(values: &[i32]) -> usize {
+        let mut index = 0;
+        do {} while let Some(&value) = values.get(index) && value > 0, do {
+            index += 1;
+        }
+        index
+    }
+}
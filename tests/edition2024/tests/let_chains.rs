@@ -0,0 +1,22 @@
+//! Let chains in `while let` conditions, which are only allowed from the 2024 edition on.
+
+use do_while_edition2024::{first_non_positive, parse_leading, sum_positive_prefix};
+
+#[test]
+fn test_let_chain() {
+    assert_eq!(sum_positive_prefix(&[3, 4, 5, 0, 6]), 12);
+    assert_eq!(sum_positive_prefix(&[-1, 4]), 0);
+    assert_eq!(sum_positive_prefix(&[]), 0);
+}
+
+#[test]
+fn test_let_chains_with_else() {
+    assert_eq!(parse_leading(&["1", "2", "x", "3"]), (vec![1, 2], false));
+    assert_eq!(parse_leading(&["4", "5"]), (vec![4, 5], true));
+}
+
+#[test]
+fn test_let_chain_in_enable() {
+    assert_eq!(first_non_positive(&[1, 2, -3, 4]), 2);
+    assert_eq!(first_non_positive(&[1, 2]), 2);
+}
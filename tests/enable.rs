@@ -0,0 +1,102 @@
+//! Loops written directly in functions wrapped in `do_while::enable!`.
+#![cfg(feature = "proc-macro")]
+
+use do_while::do_while;
+
+do_while::enable! {
+    fn sum_digits(mut n: u32) -> u32 {
+        let mut sum = 0;
+        do {
+            sum += n % 10;
+            n /= 10;
+        } while n > 0;
+        sum
+    }
+
+    fn grid(width: usize, height: usize) -> String {
+        let mut string = String::new();
+        let mut y = 0;
+        'rows: do {
+            let mut x = 0;
+            do {
+                if x * *y > 6 {
+                    continue 'rows;
+                }
+                string.push('#');
+                x += 1;
+            } until x == width;
+        } defer(y, string) {
+            *y += 1;
+            string.push('\n');
+        } while y < height;
+        string
+    }
+
+    fn join(items: &[&str]) -> String {
+        let mut string = String::new();
+        if !items.is_empty() {
+            do (let mut i = 0; i += 1) {
+                string.push_str(items[i]);
+            } while i < items.len(), do {
+                string.push_str(", ");
+            }
+        }
+        string
+    }
+
+    fn count_to_three(skip: bool) -> u32 {
+        let mut x = 0;
+        if !skip {
+            do {
+                x += 1;
+            } while x < 3;
+        }
+        x
+    }
+}
+
+struct Countdown(u32);
+
+do_while::enable! {
+    impl Countdown {
+        fn run(&mut self) -> Vec<u32> {
+            let mut seen = Vec::new();
+            #[allow(unused_doc_comments)]
+            /// Counts down to zero
+            do {
+                seen.push(self.0);
+                self.0 = self.0.saturating_sub(1);
+            } while self.0 > 0 else {
+                seen.push(0);
+            }
+
+            // Invocations of `do_while!` are left to the macro itself
+            do_while! {
+                do {
+                    self.0 += 1;
+                } while self.0 < 2;
+            }
+            seen
+        }
+    }
+}
+
+#[test]
+fn test_enable() {
+    assert_eq!(sum_digits(0), 0);
+    assert_eq!(sum_digits(1234), 10);
+
+    assert_eq!(grid(3, 3), "###\n###\n###\n");
+    assert_eq!(grid(5, 3), "#####\n#####\n####\n");
+
+    assert_eq!(join(&[]), "");
+    assert_eq!(join(&["a"]), "a");
+    assert_eq!(join(&["a", "b", "c"]), "a, b, c");
+
+    assert_eq!(count_to_three(false), 3);
+    assert_eq!(count_to_three(true), 0);
+
+    let mut countdown = Countdown(3);
+    assert_eq!(countdown.run(), [3, 2, 1, 0]);
+    assert_eq!(countdown.0, 2);
+}
@@ -0,0 +1,83 @@
+//! Invocations of `do_while!` that don't import it under its own name.
+
+mod renamed {
+    pub use do_while::do_while as dw;
+}
+
+#[test]
+fn test_qualified() {
+    let mut x = 0;
+    let mut y = 0;
+    do_while::do_while! {
+        do {
+            x += 1;
+        } while x < 10;
+
+        do {
+            y += 1;
+        } while y < x, do {
+            y += 1;
+        }
+
+        let z = x + y;
+        do |i| {
+            assert!(i < 3);
+        } until z > 0;
+    }
+    assert_eq!((x, y), (10, 11));
+
+    let value = do_while::do_while! {
+        do {
+            x -= 1;
+        } while x > 5; else x
+    };
+    assert_eq!(value, 5);
+
+    let result = do_while::try_do_while! {
+        do |i| {
+            "1".parse::<u8>()?;
+        } while i < 2;
+    };
+    assert_eq!(
+        result,
+        Ok::<_, do_while::IterationError<std::num::ParseIntError>>(())
+    );
+}
+
+#[test]
+fn test_renamed() {
+    use do_while::do_while as dw;
+
+    let mut x = 0;
+    let mut y = 0;
+    dw! {
+        do {
+            x += 1;
+        } while x < 10;
+
+        do (let mut i = 0; i += 1) {
+            y += i;
+        } while i < 4 else {
+            y *= 2;
+        }
+    }
+    assert_eq!((x, y), (10, 12));
+
+    let mut z = 0;
+    renamed::dw! {
+        do {
+            z += 1;
+        } finally {
+            x = 0;
+        } until z == 3;
+
+        z *= 2;
+    }
+    assert_eq!((x, z), (0, 6));
+}
+
+#[test]
+fn test_other_crate() {
+    assert_eq!(do_while_reexport::down_and_up!(3), (3, 3));
+    assert_eq!(do_while_reexport::down_and_up!(0), (1, 1));
+}
@@ -0,0 +1,67 @@
+//! Invocations of `do_while!` mixing every form of loop with other statements.
+
+use do_while::do_while;
+
+// Repeats the given tokens ten times and passes them on to the next macro
+macro_rules! ten {
+    ($next:ident! [$( $args:tt )*] $( $tokens:tt )*) => {
+        $next! {
+            $( $args )*
+            $( $tokens )* $( $tokens )* $( $tokens )* $( $tokens )* $( $tokens )*
+            $( $tokens )* $( $tokens )* $( $tokens )* $( $tokens )* $( $tokens )*
+        }
+    };
+}
+
+#[test]
+fn test_many_mixed_loops() {
+    // 1,000 loops of every form mixed with statements, which the procedural macro enabled by
+    // default expands one at a time
+    let (mut x, mut y, mut n) = (0, 0, 0);
+    ten! {
+        ten! [do_while! []]
+        do {
+            x += 1;
+        } while x % 2 == 1, do {
+            y += 1;
+        }
+        do {
+            x += 1;
+        } until x % 2 == 0;
+        do {
+            x += 1;
+        } until x % 2 == 0, do {
+            y += 1;
+        }
+        repeat {
+            x += 1;
+        } until x % 2 == 0;
+        repeat {
+            x += 1;
+        } until x % 2 == 0, do {
+            y += 1;
+        }
+        do {
+            n += 1;
+        } while let Some(_) = None::<u32>;
+        do {
+            n += 1;
+        } while let Some(_) = None::<u32>, do {}
+        do {
+            x += 1;
+        } while x % 2 == 1 else {
+            y += 1;
+        }
+        do {
+            x += 1;
+        } let odd = x % 2 == 1; while odd;
+        'label: do {
+            x += 1;
+            if x % 2 == 0 {
+                break 'label;
+            }
+        } while true;
+        y += 1;
+    }
+    assert_eq!((x, y, n), (1600, 500, 200));
+}
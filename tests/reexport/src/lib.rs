@@ -0,0 +1,25 @@
+pub use do_while::do_while as dw;
+
+/// Counts down from `$start` to zero and back up again, returning the number of steps each way.
+#[macro_export]
+macro_rules! down_and_up {
+    ($start:expr) => {{
+        let start: i32 = $start;
+        let mut x = start;
+        $crate::dw! {
+            let mut down = 0;
+            do {
+                x -= 1;
+                down += 1;
+            } while x > 0;
+
+            let mut up = 0;
+            do {
+                x += 1;
+                up += 1;
+            } until x == start;
+
+            (down, up)
+        }
+    }};
+}